@@ -0,0 +1,480 @@
+use crate::{config, manifest, merge, utils};
+use jsonschema::error::ValidationErrorKind;
+use serde_json::Value;
+use std::fs::read_dir;
+
+/// Embedded copy of the compose-spec schema (https://github.com/compose-spec/compose-spec).
+const SCHEMA: &str = include_str!("compose-spec.json");
+
+/// Compose files in the current directory, as picked up by `docker compose`.
+pub fn find_files() -> Vec<String> {
+    let mut files: Vec<String> = read_dir(".")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter(|name| {
+                    (name.starts_with("docker-compose") || name.starts_with("compose"))
+                        && (name.ends_with(".yaml") || name.ends_with(".yml"))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Parses compose file contents as JSON, resolving `<<` merge keys commonly used with `x-`
+/// fragments.
+pub fn parse(contents: &str) -> Result<Value, String> {
+    let mut yaml: serde_yaml::Value = serde_yaml::from_str(contents).map_err(|e| e.to_string())?;
+    yaml.apply_merge().map_err(|e| e.to_string())?;
+    serde_yaml::from_value(yaml).map_err(|e| e.to_string())
+}
+
+/// Validates compose file contents against the compose-spec, returning `line: message (at
+/// pointer)` errors.
+pub fn check(contents: &str) -> Vec<String> {
+    let value = match parse(contents) {
+        Ok(value) => value,
+        Err(e) => return vec![e],
+    };
+    let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+    let validator = jsonschema::validator_for(&schema).unwrap();
+    validator
+        .iter_errors(&value)
+        .map(|error| {
+            let pointer = error.instance_path.as_str();
+            let target = match &error.kind {
+                ValidationErrorKind::AdditionalProperties { unexpected } => {
+                    format!("{}/{}", pointer, unexpected[0])
+                }
+                _ => pointer.to_string(),
+            };
+            format!(
+                "{}: {} (at {})",
+                config::locate(contents, "yaml", &target),
+                error,
+                if pointer.is_empty() { "/" } else { pointer }
+            )
+        })
+        .collect()
+}
+
+/// Validates `files`, or every compose file in the current directory when empty.
+pub fn validate(files: &[String]) -> Result<Vec<String>, String> {
+    let files = if files.is_empty() {
+        find_files()
+    } else {
+        files.to_vec()
+    };
+    if files.is_empty() {
+        return Err("no compose file found".into());
+    }
+    let mut errors = vec![];
+    for file in &files {
+        let contents = utils::read_file(file).ok_or(format!("{}: unreadable", file))?;
+        errors.extend(
+            check(&contents)
+                .into_iter()
+                .map(|error| format!("{}:{}", file, error)),
+        );
+    }
+    Ok(errors)
+}
+
+/// Writes generated compose files, refusing output that does not satisfy the compose-spec.
+/// Nothing is written unless every file is valid, and the errors are returned as
+/// `file:line: message`.
+///
+/// The manifest records the generated contents alone as the merge base. An existing file
+/// DockerForge did not create is imported: only the entries DockerForge generates are updated
+/// in it, YAML anchors are written back expanded, and `clean` never removes it. Hand edits to
+/// the generated entries are merged with the new output, and the result is validated again.
+pub fn write(files: &[(String, String)]) -> Result<(), Vec<String>> {
+    let mut manifest = manifest::Manifest::load();
+    let mut rendered = vec![];
+    let mut errors = vec![];
+    for (file, contents) in files {
+        match render(file, contents, manifest.base(file).as_deref()) {
+            Ok(merged) => rendered.push((file, contents, merged)),
+            Err(problems) => errors.extend(
+                problems
+                    .into_iter()
+                    .map(|problem| format!("{}:{}", file, problem)),
+            ),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    for (file, generated, merged) in rendered {
+        if !manifest.entries.contains_key(file) && utils::read_file(file).is_some() {
+            manifest.imported.insert(file.clone());
+        }
+        utils::create_file(file, &merged);
+        manifest.record(file, generated);
+    }
+    manifest.save();
+    Ok(())
+}
+
+/// Generated contents reconciled with the existing `file`, if any, given the `base` contents
+/// DockerForge generated last time.
+fn render(file: &str, contents: &str, base: Option<&str>) -> Result<String, Vec<String>> {
+    let errors = check(contents);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let Some(existing) = utils::read_file(file) else {
+        return Ok(contents.to_string());
+    };
+    let (Some(mut existing), Some(generated)) = (
+        utils::Compose::parse(&existing),
+        utils::Compose::parse(contents),
+    ) else {
+        let errors = check(&existing);
+        return Err(if errors.is_empty() {
+            vec![" existing file is not a compose mapping".into()]
+        } else {
+            errors
+        });
+    };
+    match base.and_then(utils::Compose::parse) {
+        // Hand edits to the generated entries are kept, and the rest of the file can't conflict
+        // since it is absent from both the base and the new output.
+        Some(base) => {
+            let value = |compose: &utils::Compose| serde_yaml::Value::Mapping(compose.0.clone());
+            let merged = merge::merge_yaml(
+                Some(&value(&base)),
+                Some(&value(&existing)),
+                Some(&value(&generated)),
+            )
+            .map_err(|path| {
+                vec![format!(
+                    " {} was edited by hand and changed by the new output, nothing was changed",
+                    path
+                )]
+            })?;
+            existing = serde_yaml::from_value(merged.unwrap_or_default()).unwrap_or_default();
+        }
+        // Imported file: the generated entries replace the existing ones.
+        None => existing.merge(generated),
+    }
+    let merged = existing.render();
+    let errors = check(&merged);
+    if !errors.is_empty() {
+        return Err(errors
+            .into_iter()
+            .map(|error| format!("{} once merged with the existing file", error))
+            .collect());
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{languages, settings::ExtraService};
+
+    fn options() -> utils::Options<'static> {
+        utils::Options {
+            config: Some("yaml"),
+            buildkit: true,
+            private_registry: true,
+            hardened: true,
+            host_user: Some((1000, 1000)),
+            health: Some(utils::Health::parse("/health")),
+            debug: true,
+            bake: false,
+            environments: vec!["staging".into(), "production".into()],
+            env: true,
+            env_secrets: true,
+        }
+    }
+
+    /// Development compose file and environment overlays of every language.
+    fn compose_files(options: &utils::Options) -> Vec<(String, Vec<(String, String)>)> {
+        [
+            languages::rust::dockerize("app", options),
+            languages::golang::dockerize("app", options),
+            languages::python::dockerize("app", options),
+            languages::javascript::dockerize("app", options),
+            languages::typescript::dockerize("app", options),
+        ]
+        .into_iter()
+        .map(|(_, _, compose_dev, overlays)| (compose_dev, overlays))
+        .collect()
+    }
+
+    /// Merges an overlay the way `docker compose -f base -f overlay` does: mappings are merged
+    /// and sequences appended.
+    fn merge(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+        match (base, overlay) {
+            (serde_yaml::Value::Mapping(base), serde_yaml::Value::Mapping(overlay)) => {
+                for (key, value) in overlay {
+                    match base.get_mut(&key) {
+                        Some(existing) => merge(existing, value),
+                        None => {
+                            base.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (serde_yaml::Value::Sequence(base), serde_yaml::Value::Sequence(overlay)) => {
+                base.extend(overlay)
+            }
+            (base, overlay) => *base = overlay,
+        }
+    }
+
+    #[test]
+    fn generated_compose_files_match_the_spec() {
+        let options = options();
+        let services = [
+            ExtraService::new("postgres", None, None, None).unwrap(),
+            ExtraService::sidecar("proxy", "envoyproxy/envoy:v1.30"),
+        ];
+        for (compose_dev, overlays) in compose_files(&options) {
+            let compose_dev = utils::add_services(&compose_dev, &services);
+            for compose in overlays
+                .iter()
+                .map(|(_, overlay)| overlay)
+                .chain([&compose_dev])
+            {
+                assert_eq!(check(compose), Vec::<String>::new(), "{}", compose);
+            }
+        }
+    }
+
+    #[test]
+    fn environment_stacks_never_inherit_the_debugger() {
+        for (compose_dev, overlays) in compose_files(&options()) {
+            for (environment, overlay) in overlays {
+                let mut merged: serde_yaml::Value = serde_yaml::from_str(&compose_dev).unwrap();
+                merge(&mut merged, serde_yaml::from_str(&overlay).unwrap());
+                let app = &merged["services"]["app"];
+                let rendered = serde_yaml::to_string(app).unwrap();
+                assert!(
+                    !rendered.contains("SYS_PTRACE"),
+                    "{}: {}",
+                    environment,
+                    rendered
+                );
+                assert!(
+                    !rendered.contains("seccomp:unconfined"),
+                    "{}: {}",
+                    environment,
+                    rendered
+                );
+                assert!(app.get("ports").is_none(), "{}: {}", environment, rendered);
+                assert!(app.get("user").is_none(), "{}: {}", environment, rendered);
+                assert!(
+                    app.get("volumes").is_none(),
+                    "{}: {}",
+                    environment,
+                    rendered
+                );
+                assert!(merged.get("volumes").is_none(), "{}", environment);
+                assert!(
+                    app["build"]["args"].get("UID").is_none(),
+                    "{}: {}",
+                    environment,
+                    rendered
+                );
+                assert!(
+                    app.get("container_name").is_none(),
+                    "{}: {}",
+                    environment,
+                    rendered
+                );
+                let env_file = serde_yaml::to_value(utils::EnvFile::optional(&format!(
+                    ".env.{}",
+                    environment
+                )))
+                .unwrap();
+                assert!(
+                    app["env_file"]
+                        .as_sequence()
+                        .is_some_and(|files| files.contains(&env_file)),
+                    "{}: {}",
+                    environment,
+                    rendered
+                );
+            }
+        }
+        let options = options();
+        for dev_compose in [
+            languages::rust::dev_compose(&options),
+            languages::golang::dev_compose(&options),
+            languages::python::dev_compose(&options),
+            languages::javascript::dev_compose(&options),
+            languages::typescript::dev_compose(&options),
+        ] {
+            assert_eq!(check(&dev_compose), Vec::<String>::new());
+            let dev: serde_yaml::Value = serde_yaml::from_str(&dev_compose).unwrap();
+            assert_eq!(
+                dev["services"]["app"]["user"],
+                serde_yaml::Value::from("${UID:-1000}:${GID:-1000}")
+            );
+            assert_eq!(
+                dev["services"]["app"]["volumes"][0],
+                serde_yaml::Value::from(".:/app")
+            );
+        }
+        for (debug_compose, ptrace) in [
+            (languages::rust::debug_compose("app"), true),
+            (languages::golang::debug_compose(), true),
+            (languages::python::debug_compose(), false),
+            (languages::javascript::debug_compose(), false),
+            (languages::typescript::debug_compose(), false),
+        ] {
+            assert_eq!(check(&debug_compose), Vec::<String>::new());
+            assert_eq!(
+                debug_compose.contains("SYS_PTRACE"),
+                ptrace,
+                "{}",
+                debug_compose
+            );
+        }
+        // gdbserver blocks until a debugger attaches, so plain `start` must not run it.
+        let (nodemon, _, _, _) = languages::rust::dockerize("app", &options);
+        assert!(!nodemon.contains("gdbserver"), "{}", nodemon);
+        assert!(languages::rust::debug_compose("app").contains("gdbserver"));
+    }
+
+    #[test]
+    fn reports_unknown_keys_with_their_line() {
+        let compose = "services:\n  app:\n    image: app\n    restrat: always\n";
+        let errors = check(compose);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("4: "), "{}", errors[0]);
+    }
+
+    const EXISTING: &str = "\
+x-logging: &logging
+  driver: json-file
+  options:
+    max-size: 10m
+services:
+  worker:
+    image: busybox:1.36
+    logging: *logging
+    networks:
+    - backend
+  app:
+    image: app:old
+    restart: always
+networks:
+  backend: {}
+";
+
+    #[test]
+    fn imports_an_existing_compose_file() {
+        let (compose_dev, _) = compose_files(&options()).remove(0);
+        utils::tests::in_temp_dir(|| {
+            utils::create_file("docker-compose.yaml", EXISTING);
+            write(&[("docker-compose.yaml".into(), compose_dev.clone())]).unwrap();
+
+            let written: serde_yaml::Mapping =
+                serde_yaml::from_str(&utils::read_file("docker-compose.yaml").unwrap()).unwrap();
+            let keys: Vec<&str> = written.keys().filter_map(|key| key.as_str()).collect();
+            assert_eq!(keys[..3], ["x-logging", "services", "networks"]);
+            let services = written["services"].as_mapping().unwrap();
+            let names: Vec<&str> = services.keys().filter_map(|key| key.as_str()).collect();
+            assert_eq!(names, ["worker", "app"]);
+            assert_eq!(
+                services["worker"]["logging"]["options"]["max-size"],
+                serde_yaml::Value::from("10m")
+            );
+            let generated: serde_yaml::Value = serde_yaml::from_str(&compose_dev).unwrap();
+            assert_eq!(services["app"], generated["services"]["app"]);
+        });
+    }
+
+    #[test]
+    fn refuses_to_merge_into_an_invalid_file() {
+        let (compose_dev, _) = compose_files(&options()).remove(0);
+        utils::tests::in_temp_dir(|| {
+            let existing = EXISTING.replace("image: busybox:1.36", "imag: busybox:1.36");
+            utils::create_file("docker-compose.yaml", &existing);
+            let errors = write(&[("docker-compose.yaml".into(), compose_dev)]).unwrap_err();
+            assert_eq!(errors.len(), 1, "{:?}", errors);
+            assert!(errors[0].contains("imag"), "{}", errors[0]);
+            assert_eq!(utils::read_file("docker-compose.yaml").unwrap(), existing);
+        });
+    }
+
+    #[test]
+    fn clean_keeps_an_imported_compose_file() {
+        let (compose_dev, _) = compose_files(&options()).remove(0);
+        utils::tests::in_temp_dir(|| {
+            utils::create_file("docker-compose.yaml", EXISTING);
+            write(&[("docker-compose.yaml".into(), compose_dev.clone())]).unwrap();
+            let mut manifest = manifest::Manifest::load();
+            assert_eq!(
+                manifest.base("docker-compose.yaml").as_deref(),
+                Some(compose_dev.as_str())
+            );
+            assert_eq!(
+                manifest.status(),
+                [(
+                    "docker-compose.yaml".to_string(),
+                    manifest::Status::Imported
+                )]
+            );
+
+            assert_eq!(crate::clean(&mut manifest), 0);
+            let written = utils::read_file("docker-compose.yaml").unwrap();
+            assert!(written.contains("worker:"), "{}", written);
+        });
+    }
+
+    #[test]
+    fn regenerating_keeps_hand_edits_to_the_app() {
+        let options = options();
+        let (compose_dev, _) = compose_files(&options).remove(0);
+        let (regenerated, _) = compose_files(&utils::Options {
+            health: None,
+            ..options
+        })
+        .remove(0);
+        assert_ne!(compose_dev, regenerated);
+        for existing in [None, Some(EXISTING)] {
+            utils::tests::in_temp_dir(|| {
+                if let Some(existing) = existing {
+                    utils::create_file("docker-compose.yaml", existing);
+                }
+                write(&[("docker-compose.yaml".into(), compose_dev.clone())]).unwrap();
+                let mut edited: serde_yaml::Value =
+                    serde_yaml::from_str(&utils::read_file("docker-compose.yaml").unwrap())
+                        .unwrap();
+                edited["services"]["app"]["environment"] =
+                    serde_yaml::from_str("{LOG_LEVEL: debug}").unwrap();
+                utils::create_file(
+                    "docker-compose.yaml",
+                    &serde_yaml::to_string(&edited).unwrap(),
+                );
+
+                write(&[("docker-compose.yaml".into(), regenerated.clone())]).unwrap();
+                let written = utils::read_file("docker-compose.yaml").unwrap();
+                assert!(!written.contains("<<<<<<<"), "{}", written);
+                assert_eq!(check(&written), Vec::<String>::new());
+                let written: serde_yaml::Value = serde_yaml::from_str(&written).unwrap();
+                let app = &written["services"]["app"];
+                assert_eq!(
+                    app["environment"]["LOG_LEVEL"],
+                    serde_yaml::Value::from("debug")
+                );
+                assert!(app.get("healthcheck").is_none(), "{:?}", app);
+                assert_eq!(
+                    written["services"].get("worker").is_some(),
+                    existing.is_some()
+                );
+                assert_eq!(
+                    manifest::Manifest::load().base("docker-compose.yaml"),
+                    Some(regenerated.clone())
+                );
+            });
+        }
+    }
+}
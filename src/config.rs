@@ -0,0 +1,601 @@
+use crate::{k8s, languages, manifest, settings, utils};
+use jsonschema::error::ValidationErrorKind;
+use serde_json::{json, Map, Value};
+use std::fs::{read_dir, remove_file};
+
+pub const FORMATS: [&str; 4] = ["json", "yaml", "ini", "toml"];
+
+/// Generated files that may reference the config filename, besides the compose files. The
+/// scaffolded sources in `src/` are re-rendered instead, see [`rewrite_sources`].
+const ARTIFACTS: [&str; 9] = [
+    "Dockerfile",
+    "nodemon.json",
+    "Makefile",
+    "justfile",
+    "Taskfile.yml",
+    "docker-bake.hcl",
+    ".devcontainer/devcontainer.json",
+    "k8s/deployment.yaml",
+    "k8s/configmap.yaml",
+];
+
+/// Config files in the current directory as `(file, format)`, e.g. `config.production.toml`.
+pub fn find_configs() -> Vec<(String, String)> {
+    let mut configs: Vec<(String, String)> = read_dir(".")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter_map(|file| {
+                    let format = FORMATS
+                        .iter()
+                        .find(|format| file.ends_with(&format!(".{}", format)))?;
+                    (file.starts_with("config.") && !file.starts_with("config.schema."))
+                        .then(|| (file.clone(), format.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    configs.sort();
+    configs
+}
+
+pub fn parse(contents: &str, format: &str) -> Result<Value, String> {
+    match format {
+        "json" => serde_json::from_str(contents).map_err(|e| e.to_string()),
+        "yaml" => serde_yaml::from_str::<Option<Value>>(contents)
+            .map(|value| value.unwrap_or(Value::Object(Map::new())))
+            .map_err(|e| e.to_string()),
+        "toml" => toml::from_str(contents).map_err(|e| e.to_string()),
+        "ini" => parse_ini(contents),
+        _ => Err(format!("unsupported config format: {}", format)),
+    }
+}
+
+pub fn render(value: &Value, format: &str) -> Result<String, String> {
+    match format {
+        "json" => serde_json::to_string_pretty(value)
+            .map(|json| json + "\n")
+            .map_err(|e| e.to_string()),
+        "yaml" => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+        "toml" => toml::to_string(value).map_err(|e| e.to_string()),
+        "ini" => render_ini(value),
+        _ => Err(format!("unsupported config format: {}", format)),
+    }
+}
+
+/// INI values are untyped, so numbers and booleans are inferred the way most INI readers do.
+fn ini_value(raw: &str) -> Value {
+    let raw = raw.trim();
+    if let Some(quoted) = raw.strip_prefix('"').and_then(|raw| raw.strip_suffix('"')) {
+        return Value::String(quoted.to_string());
+    }
+    match raw {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => raw
+            .parse::<i64>()
+            .map(Value::from)
+            .or_else(|_| raw.parse::<f64>().map(Value::from))
+            .unwrap_or_else(|_| Value::String(raw.to_string())),
+    }
+}
+
+fn parse_ini(contents: &str) -> Result<Value, String> {
+    let mut root = Map::new();
+    let mut section: Option<String> = None;
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            root.insert(name.trim().to_string(), Value::Object(Map::new()));
+            section = Some(name.trim().to_string());
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(format!("line {}: expected `key = value`", number + 1))?;
+        let target = match &section {
+            Some(name) => root[name].as_object_mut().unwrap(),
+            None => &mut root,
+        };
+        target.insert(key.trim().to_string(), ini_value(value));
+    }
+    Ok(Value::Object(root))
+}
+
+fn render_ini(value: &Value) -> Result<String, String> {
+    let scalar = |key: &str, value: &Value| match value {
+        Value::String(string) => Ok(format!("{} = {}\n", key, string)),
+        Value::Number(_) | Value::Bool(_) => Ok(format!("{} = {}\n", key, value)),
+        _ => Err(format!("`{}` cannot be represented in INI", key)),
+    };
+    let root = value
+        .as_object()
+        .ok_or("INI config must be a table of sections")?;
+
+    let mut contents = String::new();
+    for (key, value) in root.iter().filter(|(_, value)| !value.is_object()) {
+        contents.push_str(&scalar(key, value)?);
+    }
+    for (name, section) in root.iter().filter(|(_, value)| value.is_object()) {
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+        contents.push_str(&format!("[{}]\n", name));
+        for (key, value) in section.as_object().unwrap() {
+            contents.push_str(
+                &scalar(key, value)
+                    .map_err(|_| format!("`{}.{}` cannot be represented in INI", name, key))?,
+            );
+        }
+    }
+    Ok(contents)
+}
+
+pub struct Conversion {
+    /// `(old, new)` config file names.
+    pub renames: Vec<(String, String)>,
+    /// Scaffolded sources re-rendered to load the new format.
+    pub sources: Vec<&'static str>,
+    /// Regenerated files left with conflicting hand edits.
+    pub conflicts: Vec<String>,
+}
+
+/// Converts every config file to `to`, rewrites the generated files referencing them and
+/// switches the saved settings over to the new format.
+pub fn convert(to: &str) -> Result<Conversion, String> {
+    if !FORMATS.contains(&to) {
+        return Err(format!("unsupported config format: {}", to));
+    }
+    let configs = find_configs();
+    if configs.is_empty() {
+        return Err("no config file found".into());
+    }
+    let from = &configs[0].1;
+    if configs.iter().any(|(_, format)| format != from) {
+        return Err("config files use more than one format".into());
+    }
+    if from == to {
+        return Ok(Conversion {
+            renames: vec![],
+            sources: vec![],
+            conflicts: vec![],
+        });
+    }
+    let settings = settings::Settings::load()?;
+
+    // Render everything first so a parse error or a hand written loader leaves the project
+    // untouched.
+    let converted = configs
+        .iter()
+        .map(|(file, format)| {
+            let contents = utils::read_file(file).ok_or(format!("{}: unreadable", file))?;
+            let value = parse(&contents, format).map_err(|e| format!("{}: {}", file, e))?;
+            let rendered = render(&value, to).map_err(|e| format!("{}: {}", file, e))?;
+            let new = format!("{}{}", file.strip_suffix(format.as_str()).unwrap(), to);
+            Ok((file.clone(), new, rendered))
+        })
+        .collect::<Result<Vec<(String, String, String)>, String>>()?;
+    let sources = rewrite_sources(settings.as_ref(), from, to)?;
+
+    let mut manifest = manifest::Manifest::load();
+    let status = manifest.status();
+    for (old, new, contents) in &converted {
+        utils::create_file(new, contents);
+        let _ = remove_file(old);
+        // A config file left as `init` wrote it stays tracked under its new name.
+        if manifest.entries.contains_key(old) {
+            manifest.forget(old);
+            if status.contains(&(old.clone(), manifest::Status::Untouched)) {
+                manifest.record(new, contents);
+            }
+        }
+    }
+    for (file, contents) in &sources {
+        utils::create_file(file, contents);
+        if manifest.entries.contains_key(*file) {
+            manifest.record(file, contents);
+        }
+    }
+    if !manifest.entries.is_empty() {
+        manifest.save();
+    }
+
+    let renames: Vec<(String, String)> = converted
+        .into_iter()
+        .map(|(old, new, _)| (old, new))
+        .collect();
+    rewrite_references(&renames, from, to);
+    let mut conflicts = vec![];
+    if let Some(mut settings) = settings {
+        settings.config = to.to_string();
+        settings.save();
+        // The ConfigMap embeds the production config, so it is regenerated rather than rewritten.
+        if settings.k8s {
+            let before = manifest::snapshot(
+                &k8s::files(true)
+                    .into_iter()
+                    .map(String::from)
+                    .collect::<Vec<_>>(),
+            );
+            k8s::write(&settings.name, &settings.options());
+            let mut manifest = manifest::Manifest::load();
+            conflicts = manifest.reconcile(before);
+            manifest.save();
+        }
+    }
+    Ok(Conversion {
+        renames,
+        sources: sources.into_iter().map(|(file, _)| file).collect(),
+        conflicts,
+    })
+}
+
+/// Scaffolded sources re-rendered for `to`, as long as they are still exactly as scaffolded.
+///
+/// Fails when a source in `src/` loads `config.{from}` but cannot be re-rendered, since the app
+/// would no longer find its config after the conversion.
+fn rewrite_sources(
+    settings: Option<&settings::Settings>,
+    from: &str,
+    to: &str,
+) -> Result<Vec<(&'static str, String)>, String> {
+    if let Some(settings) = settings {
+        let options = settings.options();
+        let render = |format| {
+            languages::scaffold(
+                &settings.language,
+                &settings.name,
+                options.health.as_ref(),
+                Some(format),
+            )
+        };
+        let changed: Vec<_> = render(from)
+            .into_iter()
+            .zip(render(to))
+            .filter(|((_, old), (_, new))| old != new)
+            .collect();
+        if !changed.is_empty()
+            && changed
+                .iter()
+                .all(|((file, old), _)| utils::read_file(file).as_ref() == Some(old))
+        {
+            return Ok(changed.into_iter().map(|(_, new)| new).collect());
+        }
+    }
+
+    let mut dirs = vec![std::path::PathBuf::from("src")];
+    while let Some(dir) = dirs.pop() {
+        for path in read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        {
+            if path.is_dir() {
+                dirs.push(path);
+            } else if std::fs::read_to_string(&path).is_ok_and(|contents| {
+                replace_path(&contents, &format!("config.{}", from), "") != contents
+            }) {
+                return Err(format!(
+                    "{} loads config.{}, update its config loader to read config.{} as {} first",
+                    path.display(),
+                    from,
+                    to,
+                    to.to_uppercase()
+                ));
+            }
+        }
+    }
+    Ok(vec![])
+}
+
+/// Replaces `from` where it is a whole file name: preceded by `/`, whitespace, a quote or
+/// nothing, and not followed by a word character. `config.json` is then left alone inside
+/// `tsconfig.json` or `config.json5`.
+fn replace_path(contents: &str, from: &str, to: &str) -> String {
+    let mut replaced = String::with_capacity(contents.len());
+    let mut last = 0;
+    for (index, _) in contents.match_indices(from) {
+        let end = index + from.len();
+        let starts = contents[..index]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c == '/' || c == '"' || c == '\'' || c.is_whitespace());
+        let ends = contents[end..]
+            .chars()
+            .next()
+            .is_none_or(|c| !(c.is_alphanumeric() || c == '_' || c == '-'));
+        if starts && ends {
+            replaced.push_str(&contents[last..index]);
+            replaced.push_str(to);
+            last = end;
+        }
+    }
+    replaced.push_str(&contents[last..]);
+    replaced
+}
+
+fn rewrite_references(renames: &[(String, String)], from: &str, to: &str) {
+    let mut manifest = manifest::Manifest::load();
+    let compose_files = read_dir(".")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter(|file| file.starts_with("docker-compose") && file.ends_with(".yaml"))
+                .collect::<Vec<String>>()
+        })
+        .unwrap_or_default();
+
+    for file in ARTIFACTS
+        .iter()
+        .map(|file| file.to_string())
+        .chain(compose_files)
+    {
+        let Some(contents) = utils::read_file(&file) else {
+            continue;
+        };
+        let rewrite = |contents: &str| {
+            let renamed = renames
+                .iter()
+                .fold(contents.to_string(), |contents, (old, new)| {
+                    replace_path(&contents, old, new)
+                });
+            replace_path(
+                &renamed,
+                &format!("config.{}", from),
+                &format!("config.{}", to),
+            )
+        };
+        let rewritten = rewrite(&contents);
+        if rewritten == contents {
+            continue;
+        }
+        utils::create_file(&file, &rewritten);
+        // Rewrite the merge base the same way, so the file keeps its untouched/modified status.
+        if manifest.entries.contains_key(&file) {
+            let base = manifest.base(&file).map(|base| rewrite(&base));
+            manifest.record(&file, &base.unwrap_or(rewritten));
+        }
+    }
+    if !manifest.entries.is_empty() {
+        manifest.save();
+    }
+}
+
+pub const SCHEMA_FILE: &str = "config.schema.json";
+
+/// JSON Schema for the settings written by `utils::AppConfig`.
+pub fn schema() -> Value {
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "Application config",
+        "type": "object",
+        "required": ["app"],
+        "properties": {
+            "app": {
+                "type": "object",
+                "required": ["port", "log_level", "database_url"],
+                "additionalProperties": false,
+                "properties": {
+                    "port": {
+                        "description": "Port the application listens on",
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 65535,
+                    },
+                    "log_level": {
+                        "description": "Minimum level of emitted log records",
+                        "type": "string",
+                        "enum": ["trace", "debug", "info", "warn", "error"],
+                    },
+                    "database_url": {
+                        "description": "Database connection URL",
+                        "type": "string",
+                        "pattern": "^[a-zA-Z][a-zA-Z0-9+.-]*://",
+                    },
+                },
+            },
+        },
+    })
+}
+
+pub fn write_schema() {
+    utils::create_file(
+        SCHEMA_FILE,
+        &(serde_json::to_string_pretty(&schema()).unwrap() + "\n"),
+    );
+}
+
+/// Best effort 1-based line of the value at a JSON pointer, found by walking the keys in order.
+pub fn locate(contents: &str, format: &str, pointer: &str) -> usize {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut line = 0;
+    for segment in pointer.split('/').skip(1) {
+        let key = segment.replace("~1", "/").replace("~0", "~");
+        if key.parse::<usize>().is_ok() {
+            continue;
+        }
+        let matches = |text: &str| {
+            let text = text.trim_start().trim_start_matches("- ");
+            let rest = match format {
+                "json" => text.strip_prefix(&format!("\"{}\"", key)),
+                "yaml" => text
+                    .strip_prefix(&format!("\"{}\"", key))
+                    .or_else(|| text.strip_prefix(key.as_str())),
+                _ => {
+                    if let Some(header) = text.strip_prefix('[') {
+                        let header = header.trim_start_matches('[');
+                        let name = header.split(']').next().unwrap_or_default();
+                        return name.rsplit('.').next().map(str::trim) == Some(key.as_str());
+                    }
+                    text.strip_prefix(key.as_str())
+                }
+            };
+            let separator = if matches!(format, "json" | "yaml") {
+                ':'
+            } else {
+                '='
+            };
+            rest.is_some_and(|rest| rest.trim_start().starts_with(separator))
+        };
+        match lines.iter().skip(line).position(|text| matches(text)) {
+            Some(offset) => line += offset,
+            None => break,
+        }
+    }
+    line + 1
+}
+
+/// Validates every config file against the schema, returning `file:line: message` errors.
+pub fn validate() -> Result<Vec<String>, String> {
+    let configs = find_configs();
+    if configs.is_empty() {
+        return Err("no config file found".into());
+    }
+    let schema = utils::read_file(SCHEMA_FILE)
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_else(schema);
+    let validator =
+        jsonschema::validator_for(&schema).map_err(|e| format!("{}: {}", SCHEMA_FILE, e))?;
+
+    let mut errors = vec![];
+    for (file, format) in &configs {
+        let contents = utils::read_file(file).ok_or(format!("{}: unreadable", file))?;
+        let value = match parse(&contents, format) {
+            Ok(value) => value,
+            Err(e) => {
+                errors.push(format!("{}: {}", file, e));
+                continue;
+            }
+        };
+        for error in validator.iter_errors(&value) {
+            let pointer = error.instance_path.as_str();
+            // Point unexpected keys at their own line rather than at the enclosing table.
+            let target = match &error.kind {
+                ValidationErrorKind::AdditionalProperties { unexpected } => {
+                    format!("{}/{}", pointer, unexpected[0])
+                }
+                _ => pointer.to_string(),
+            };
+            errors.push(format!(
+                "{}:{}: {} (at {})",
+                file,
+                locate(&contents, format, &target),
+                error,
+                if pointer.is_empty() { "/" } else { pointer }
+            ));
+        }
+    }
+    Ok(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::in_temp_dir;
+
+    fn forge_typescript() {
+        utils::create_file(".gitignore", "");
+        std::fs::create_dir_all(settings::SETTINGS_DIR).unwrap();
+        utils::create_file(
+            settings::SETTINGS_FILE,
+            r#"version = "1.0.0"
+name = "app"
+language = "Typescript"
+config = "json"
+scaffold = true
+env = true
+env_secrets = false
+runner = "make"
+buildkit = true
+private_registry = false
+hardened = false
+map_user = false
+debug = false
+devcontainer = false
+bake = false
+platforms = ["linux/amd64"]
+environments = ["production"]
+k8s = true
+"#,
+        );
+        crate::forge(&settings::Settings::load().unwrap().unwrap(), true);
+    }
+
+    #[test]
+    fn convert_switches_the_project_to_the_new_format() {
+        in_temp_dir(|| {
+            forge_typescript();
+            let dockerfile = utils::read_file("Dockerfile").unwrap();
+            assert!(dockerfile.contains("tsconfig.json"));
+            let tracked = manifest::Manifest::load().entries;
+            for file in [
+                ".env",
+                ".env.example",
+                ".env.production",
+                "config.development.json",
+                "config.production.json",
+                "src/index.ts",
+                "package.json",
+            ] {
+                assert!(tracked.contains_key(file), "{}", file);
+            }
+            assert!(!tracked.contains_key(".gitignore"));
+
+            let conversion = convert("toml").unwrap();
+            assert!(!conversion.renames.is_empty());
+            assert!(conversion.sources.contains(&"src/index.ts"));
+            assert!(conversion.conflicts.is_empty());
+            assert!(find_configs().iter().all(|(_, format)| format == "toml"));
+            assert_eq!(settings::Settings::load().unwrap().unwrap().config, "toml");
+            assert!(utils::read_file("docker-compose.yaml")
+                .unwrap()
+                .contains("config.development.toml"));
+            assert!(utils::read_file("src/index.ts")
+                .unwrap()
+                .contains("\"config.toml\""));
+            assert_eq!(utils::read_file("Dockerfile").unwrap(), dockerfile);
+            let configmap = utils::read_file("k8s/configmap.yaml").unwrap();
+            assert!(configmap.contains("config.toml: |"), "{}", configmap);
+            assert!(!configmap.contains('{'), "{}", configmap);
+            assert!(utils::read_file("k8s/deployment.yaml")
+                .unwrap()
+                .contains("/app/config.toml"));
+            let manifest = manifest::Manifest::load();
+            assert!(manifest.entries.contains_key("config.production.toml"));
+            assert!(!manifest.entries.contains_key("config.production.json"));
+            for (file, status) in manifest.status() {
+                assert_eq!(status, manifest::Status::Untouched, "{}", file);
+            }
+        });
+    }
+
+    #[test]
+    fn convert_refuses_to_orphan_a_hand_written_loader() {
+        in_temp_dir(|| {
+            forge_typescript();
+            let index = utils::read_file("src/index.ts").unwrap() + "// edited\n";
+            utils::create_file("src/index.ts", &index);
+
+            let error = convert("yaml").err().unwrap();
+            assert!(error.contains("src/index.ts"), "{}", error);
+            assert!(find_configs().iter().all(|(_, format)| format == "json"));
+            assert_eq!(utils::read_file("src/index.ts").unwrap(), index);
+            assert_eq!(settings::Settings::load().unwrap().unwrap().config, "json");
+        });
+    }
+
+    #[test]
+    fn replaces_whole_file_names_only() {
+        assert_eq!(
+            replace_path(
+                "COPY tsconfig.json ./\nwatch: [\"config.json\"]\n- ./config.json:/app/config.json\nconfig.json5",
+                "config.json",
+                "config.toml"
+            ),
+            "COPY tsconfig.json ./\nwatch: [\"config.toml\"]\n- ./config.toml:/app/config.toml\nconfig.json5"
+        );
+    }
+}
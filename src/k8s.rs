@@ -0,0 +1,1072 @@
+use crate::{compose, lint, utils};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs::create_dir_all;
+
+pub const DIR: &str = "k8s";
+
+pub fn files(config: bool) -> Vec<&'static str> {
+    let mut files = vec!["k8s/deployment.yaml", "k8s/service.yaml"];
+    if config {
+        files.push("k8s/configmap.yaml");
+    }
+    files
+}
+
+fn labels(name: &str) -> Value {
+    json!({ "app.kubernetes.io/name": name })
+}
+
+/// Compose memory size (`512M`, `1g`, `1gb`) as a Kubernetes quantity.
+fn memory(size: &str) -> String {
+    let size = size.trim_end_matches(['b', 'B']);
+    match size.chars().last().map(|unit| unit.to_ascii_uppercase()) {
+        Some(unit @ ('K' | 'M' | 'G' | 'T')) => format!("{}{}i", &size[..size.len() - 1], unit),
+        _ => size.to_string(),
+    }
+}
+
+fn probe(health: &utils::Health) -> Value {
+    let mut probe = match health {
+        utils::Health::Http { port, path } => json!({
+            "httpGet": { "path": path, "port": port },
+        }),
+        utils::Health::Tcp { port } => json!({
+            "tcpSocket": { "port": port },
+        }),
+        utils::Health::Command(command) => json!({
+            "exec": { "command": ["sh", "-c", command] },
+        }),
+    };
+    probe["periodSeconds"] = json!(30);
+    probe["timeoutSeconds"] = json!(5);
+    probe["failureThreshold"] = json!(3);
+    probe
+}
+
+/// Deployment, Service and (with a config file) ConfigMap for the production image.
+pub fn manifests(name: &str, options: &utils::Options) -> Vec<(&'static str, String)> {
+    let port = options
+        .health
+        .as_ref()
+        .and_then(|h| h.port())
+        .unwrap_or(utils::APP_PORT);
+    let (_, deploy) = utils::environment("production");
+
+    let mut container = json!({
+        "name": "app",
+        "image": format!("{}-app:latest", name),
+        "ports": [{ "containerPort": port }],
+        "resources": {
+            "limits": {
+                "cpu": deploy.resources.limits.cpus,
+                "memory": memory(&deploy.resources.limits.memory),
+            },
+        },
+    });
+    if let Some(health) = &options.health {
+        container["livenessProbe"] = probe(health);
+        container["readinessProbe"] = probe(health);
+    }
+    if options.hardened {
+        container["securityContext"] = json!({
+            "runAsNonRoot": true,
+            "readOnlyRootFilesystem": true,
+            "allowPrivilegeEscalation": false,
+            "capabilities": { "drop": ["ALL"] },
+        });
+    }
+
+    let mut pod = json!({ "containers": [] });
+    let mut configmap = None;
+    if let Some(format) = options.config {
+        let file = format!("config.{}", format);
+        container["volumeMounts"] = json!([{
+            "name": "config",
+            "mountPath": format!("/app/{}", file),
+            "subPath": file,
+        }]);
+        pod["volumes"] = json!([{
+            "name": "config",
+            "configMap": { "name": format!("{}-config", name) },
+        }]);
+        let contents =
+            utils::read_file(&format!("config.production.{}", format)).unwrap_or_default();
+        configmap = Some((
+            "k8s/configmap.yaml",
+            json!({
+                "apiVersion": "v1",
+                "kind": "ConfigMap",
+                "metadata": { "name": format!("{}-config", name), "labels": labels(name) },
+                "data": { file: contents },
+            }),
+        ));
+    }
+    pod["containers"] = json!([container]);
+
+    let manifests = [
+        (
+            "k8s/deployment.yaml",
+            json!({
+                "apiVersion": "apps/v1",
+                "kind": "Deployment",
+                "metadata": { "name": name, "labels": labels(name) },
+                "spec": {
+                    "replicas": deploy.replicas,
+                    "selector": { "matchLabels": labels(name) },
+                    "template": {
+                        "metadata": { "labels": labels(name) },
+                        "spec": pod,
+                    },
+                },
+            }),
+        ),
+        (
+            "k8s/service.yaml",
+            json!({
+                "apiVersion": "v1",
+                "kind": "Service",
+                "metadata": { "name": name, "labels": labels(name) },
+                "spec": {
+                    "selector": labels(name),
+                    "ports": [{ "port": 80, "targetPort": port }],
+                },
+            }),
+        ),
+    ];
+
+    manifests
+        .into_iter()
+        .chain(configmap)
+        .map(|(file, manifest)| (file, serde_yaml::to_string(&manifest).unwrap()))
+        .collect()
+}
+
+pub fn write(name: &str, options: &utils::Options) {
+    create_dir_all(DIR).unwrap();
+    for (file, contents) in manifests(name, options) {
+        utils::create_file(file, &contents);
+    }
+}
+
+/// Service keys translated by [`convert`], any other key is reported as unsupported.
+const CONVERTED_KEYS: [&str; 22] = [
+    "image",
+    "build",
+    "container_name",
+    "command",
+    "entrypoint",
+    "working_dir",
+    "environment",
+    "env_file",
+    "configs",
+    "secrets",
+    "ports",
+    "expose",
+    "volumes",
+    "healthcheck",
+    "depends_on",
+    "deploy",
+    "restart",
+    "user",
+    "cap_add",
+    "cap_drop",
+    "read_only",
+    "privileged",
+];
+
+/// Kubernetes resource name (RFC 1123 label) for a compose service or volume name.
+fn resource_name(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+/// Seconds in a compose duration such as `1m30s` or `500ms`, rounded up.
+fn seconds(duration: &str) -> Option<u64> {
+    let mut millis = 0;
+    let mut number = String::new();
+    let mut chars = duration.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let value: f64 = number.parse().ok()?;
+        number.clear();
+        let unit = match c {
+            'h' => 3_600_000.0,
+            'm' if chars.peek() == Some(&'s') => {
+                chars.next();
+                1.0
+            }
+            'm' => 60_000.0,
+            's' => 1000.0,
+            _ => return None,
+        };
+        millis += (value * unit) as u64;
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    Some(millis.div_ceil(1000).max(1))
+}
+
+/// `command`/`entrypoint` value as an argument list.
+fn arguments(value: &Value) -> Vec<Value> {
+    match value {
+        Value::String(command) => command.split_whitespace().map(|arg| json!(arg)).collect(),
+        Value::Array(args) => args.clone(),
+        _ => vec![],
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Null => None,
+        value => Some(value.to_string()),
+    }
+}
+
+/// Port published by a compose service, as `(service port, container port, protocol)`.
+fn ports(service: &Value, warnings: &mut Vec<String>, name: &str) -> Vec<(u64, u64, String)> {
+    let mut ports = vec![];
+    for port in service["ports"].as_array().into_iter().flatten() {
+        let parsed = match port {
+            Value::Number(target) => target.as_u64().map(|target| (target, target, "TCP".into())),
+            Value::String(port) => {
+                let (port, protocol) = port.split_once('/').unwrap_or((port, "tcp"));
+                let parts: Vec<&str> = port.rsplitn(3, ':').collect();
+                let target = parts[0].parse().ok();
+                let published = match parts.get(1) {
+                    Some(published) => published.parse().ok(),
+                    None => target,
+                };
+                target
+                    .zip(published)
+                    .map(|(target, published)| (published, target, protocol.to_uppercase()))
+            }
+            Value::Object(_) => {
+                let target = port["target"]
+                    .as_u64()
+                    .or_else(|| port["target"].as_str()?.parse().ok());
+                let published = port["published"]
+                    .as_u64()
+                    .or_else(|| port["published"].as_str()?.parse().ok())
+                    .or(target);
+                let protocol = port["protocol"].as_str().unwrap_or("tcp").to_uppercase();
+                target
+                    .zip(published)
+                    .map(|(target, published)| (published, target, protocol))
+            }
+            _ => None,
+        };
+        match parsed {
+            Some(port) => ports.push(port),
+            None => warnings.push(format!(
+                "services.{}.ports: {} is not supported (port ranges have no Kubernetes equivalent)",
+                name, port
+            )),
+        }
+    }
+    for port in service["expose"].as_array().into_iter().flatten() {
+        let target = port
+            .as_u64()
+            .or_else(|| port.as_str()?.split('/').next()?.parse().ok());
+        if let Some(target) = target {
+            ports.push((target, target, "TCP".into()));
+        }
+    }
+    ports
+}
+
+/// Liveness/readiness probe for a compose healthcheck.
+fn healthcheck_probe(healthcheck: &Value) -> Option<Value> {
+    if healthcheck["disable"].as_bool() == Some(true) {
+        return None;
+    }
+    let command: Vec<Value> = match &healthcheck["test"] {
+        Value::String(command) => vec![json!("sh"), json!("-c"), json!(command)],
+        Value::Array(test) => match test.first().and_then(Value::as_str) {
+            Some("CMD") => test[1..].to_vec(),
+            Some("CMD-SHELL") => vec![json!("sh"), json!("-c"), test.get(1)?.clone()],
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let mut probe = json!({ "exec": { "command": command } });
+    let timing = [
+        ("interval", "periodSeconds"),
+        ("timeout", "timeoutSeconds"),
+        ("start_period", "initialDelaySeconds"),
+    ];
+    for (key, field) in timing {
+        if let Some(value) = healthcheck[key].as_str().and_then(seconds) {
+            probe[field] = json!(value);
+        }
+    }
+    if let Some(retries) = healthcheck["retries"].as_u64() {
+        probe["failureThreshold"] = json!(retries);
+    }
+    Some(probe)
+}
+
+pub struct Conversion {
+    /// Manifest contents by file name.
+    pub manifests: Vec<(String, String)>,
+    /// Compose features that have no Kubernetes equivalent.
+    pub warnings: Vec<String>,
+}
+
+/// ConfigMaps and Secrets holding the file backed top-level `configs` and `secrets`, returned
+/// as `(resource, key)` by `(section, name)` for the services mounting them.
+fn file_sources(
+    compose: &Value,
+    manifests: &mut Vec<(String, Value)>,
+    warnings: &mut Vec<String>,
+) -> BTreeMap<(&'static str, String), (String, String)> {
+    let mut sources = BTreeMap::new();
+    for (section, kind, create) in [
+        ("configs", "ConfigMap", "configmap"),
+        ("secrets", "Secret", "secret generic"),
+    ] {
+        for (name, definition) in compose[section].as_object().into_iter().flatten() {
+            let resource = resource_name(name);
+            let (key, contents) = match (definition["file"].as_str(), &definition["content"]) {
+                (Some(file), _) => {
+                    let key = std::path::Path::new(file)
+                        .file_name()
+                        .and_then(|key| key.to_str())
+                        .unwrap_or(name)
+                        .to_string();
+                    let contents = utils::read_file(file);
+                    if contents.is_none() {
+                        warnings.push(format!(
+                            "{}.{}: {} is unreadable, create the {} {} with `kubectl create {} {} --from-file={}={}`",
+                            section,
+                            name,
+                            file,
+                            resource,
+                            kind,
+                            create,
+                            resource,
+                            key,
+                            file
+                        ));
+                    }
+                    (key, contents)
+                }
+                (None, Value::String(content)) => (name.clone(), Some(content.clone())),
+                _ => {
+                    warnings.push(format!(
+                        "{}.{} is not backed by a file, skipped",
+                        section, name
+                    ));
+                    continue;
+                }
+            };
+            if let Some(contents) = contents {
+                let data = json!({ key.as_str(): contents });
+                let (file, manifest) = match kind {
+                    "ConfigMap" => (
+                        format!("{}-config.yaml", resource),
+                        json!({
+                            "apiVersion": "v1",
+                            "kind": kind,
+                            "metadata": { "name": resource, "labels": labels(&resource) },
+                            "data": data,
+                        }),
+                    ),
+                    _ => {
+                        let file = format!("{}-secret.yaml", resource);
+                        warnings.push(format!(
+                            "secrets.{} moved to the {} Secret, keep {} out of version control",
+                            name, resource, file
+                        ));
+                        (
+                            file,
+                            json!({
+                                "apiVersion": "v1",
+                                "kind": kind,
+                                "metadata": { "name": resource, "labels": labels(&resource) },
+                                "type": "Opaque",
+                                "stringData": data,
+                            }),
+                        )
+                    }
+                };
+                manifests.push((file, manifest));
+            }
+            sources.insert((section, name.clone()), (resource, key));
+        }
+    }
+    sources
+}
+
+/// Translates a compose file into Deployments, Services, PersistentVolumeClaims, ConfigMaps and
+/// Secrets.
+pub fn convert(contents: &str) -> Result<Conversion, String> {
+    let compose = compose::parse(contents)?;
+    let services = compose["services"]
+        .as_object()
+        .ok_or("no services defined")?;
+    let mut warnings = vec![];
+    for key in compose.as_object().into_iter().flat_map(Map::keys) {
+        if !matches!(
+            key.as_str(),
+            "services" | "volumes" | "configs" | "secrets" | "name" | "version"
+        ) && !key.starts_with("x-")
+        {
+            warnings.push(format!("top-level {} are not converted", key));
+        }
+    }
+
+    // Service ports are needed up front so depends_on can wait on them.
+    let service_ports: BTreeMap<&String, Vec<(u64, u64, String)>> = services
+        .iter()
+        .map(|(name, service)| (name, ports(service, &mut warnings, name)))
+        .collect();
+
+    let mut manifests = vec![];
+    let sources = file_sources(&compose, &mut manifests, &mut warnings);
+    let mut claims: Vec<String> = vec![];
+    for (name, service) in services {
+        let resource = resource_name(name);
+        let unsupported = service
+            .as_object()
+            .into_iter()
+            .flat_map(Map::keys)
+            .filter(|key| !CONVERTED_KEYS.contains(&key.as_str()) && !key.starts_with("x-"));
+        for key in unsupported {
+            warnings.push(format!(
+                "services.{}.{} has no Kubernetes equivalent",
+                name, key
+            ));
+        }
+
+        let image = match (service["image"].as_str(), service.get("build")) {
+            (Some(image), _) => image.to_string(),
+            (None, Some(_)) => {
+                warnings.push(format!(
+                    "services.{}.build: assuming image {}:latest, build and push it to a registry the cluster can pull from",
+                    name, resource
+                ));
+                format!("{}:latest", resource)
+            }
+            (None, None) => {
+                warnings.push(format!("services.{} has no image, skipped", name));
+                continue;
+            }
+        };
+
+        let mut container = json!({ "name": resource, "image": image });
+        if let Some(entrypoint) = service.get("entrypoint") {
+            container["command"] = json!(arguments(entrypoint));
+        }
+        if let Some(command) = service.get("command") {
+            container["args"] = json!(arguments(command));
+        }
+        if let Some(working_dir) = service["working_dir"].as_str() {
+            container["workingDir"] = json!(working_dir);
+        }
+
+        let ports = &service_ports[name];
+        if !ports.is_empty() {
+            container["ports"] = ports
+                .iter()
+                .map(|(_, target, protocol)| json!({ "containerPort": target, "protocol": protocol }))
+                .collect();
+        }
+
+        let environment: BTreeMap<String, String> = match &service["environment"] {
+            Value::Object(variables) => variables
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), scalar(value)?)))
+                .collect(),
+            Value::Array(variables) => variables
+                .iter()
+                .filter_map(|variable| {
+                    let (key, value) = variable.as_str()?.split_once('=')?;
+                    Some((key.to_string(), value.to_string()))
+                })
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+        let (secrets, environment): (BTreeMap<_, _>, BTreeMap<_, _>) = environment
+            .into_iter()
+            .partition(|(key, _)| lint::is_secret(key));
+        let mut env_from = vec![];
+        if !environment.is_empty() {
+            let configmap = format!("{}-env", resource);
+            env_from.push(json!({ "configMapRef": { "name": configmap } }));
+            manifests.push((
+                format!("{}-configmap.yaml", resource),
+                json!({
+                    "apiVersion": "v1",
+                    "kind": "ConfigMap",
+                    "metadata": { "name": configmap, "labels": labels(&resource) },
+                    "data": environment,
+                }),
+            ));
+        }
+        if !secrets.is_empty() {
+            let secret = format!("{}-secret", resource);
+            let file = format!("{}-secret.yaml", resource);
+            warnings.push(format!(
+                "services.{}.environment: {} moved to the {} Secret, keep {} out of version control",
+                name,
+                secrets.keys().cloned().collect::<Vec<_>>().join(", "),
+                secret,
+                file
+            ));
+            env_from.push(json!({ "secretRef": { "name": secret } }));
+            manifests.push((
+                file,
+                json!({
+                    "apiVersion": "v1",
+                    "kind": "Secret",
+                    "metadata": { "name": secret, "labels": labels(&resource) },
+                    "type": "Opaque",
+                    "stringData": secrets,
+                }),
+            ));
+        }
+        // Env files stay out of the manifests, the Secret is created from them by hand.
+        let env_files: Vec<(&str, bool)> = match &service["env_file"] {
+            Value::String(file) => vec![(file.as_str(), true)],
+            Value::Array(files) => files
+                .iter()
+                .filter_map(|file| match file {
+                    Value::String(file) => Some((file.as_str(), true)),
+                    _ => Some((file["path"].as_str()?, file["required"] != json!(false))),
+                })
+                .collect(),
+            _ => vec![],
+        };
+        if !env_files.is_empty() {
+            let secret = format!("{}-env-file", resource);
+            let files: Vec<&str> = env_files.iter().map(|(file, _)| *file).collect();
+            warnings.push(format!(
+                "services.{}.env_file: {} is not converted, create the {} Secret with `kubectl create secret generic {} {}`",
+                name,
+                files.join(", "),
+                secret,
+                secret,
+                files
+                    .iter()
+                    .map(|file| format!("--from-env-file={}", file))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ));
+            let mut secret_ref = json!({ "name": secret });
+            if env_files.iter().all(|(_, required)| !required) {
+                secret_ref["optional"] = json!(true);
+            }
+            env_from.push(json!({ "secretRef": secret_ref }));
+        }
+        if !env_from.is_empty() {
+            container["envFrom"] = json!(env_from);
+        }
+        let mut mounts = vec![];
+        let mut volumes = vec![];
+        for volume in service["volumes"].as_array().into_iter().flatten() {
+            let (kind, source, target, read_only) = match volume {
+                Value::String(volume) => {
+                    let parts: Vec<&str> = volume.split(':').collect();
+                    let (source, target) = match parts.as_slice() {
+                        [target] => (None, *target),
+                        [source, target, ..] => (Some(*source), *target),
+                        [] => continue,
+                    };
+                    let kind = match source {
+                        Some(source) if source.starts_with(['.', '/', '~', '$']) => "bind",
+                        _ => "volume",
+                    };
+                    (kind, source, target, parts.get(2) == Some(&"ro"))
+                }
+                Value::Object(_) => (
+                    volume["type"].as_str().unwrap_or("volume"),
+                    volume["source"].as_str(),
+                    volume["target"].as_str().unwrap_or_default(),
+                    volume["read_only"].as_bool().unwrap_or(false),
+                ),
+                _ => continue,
+            };
+            let volume_name = format!("{}-{}", resource, volumes.len());
+            let definition = match (kind, source) {
+                ("volume", Some(source)) => {
+                    let claim = resource_name(source);
+                    if !claims.contains(&claim) {
+                        claims.push(claim.clone());
+                    }
+                    json!({ "name": volume_name, "persistentVolumeClaim": { "claimName": claim } })
+                }
+                ("volume", None) => json!({ "name": volume_name, "emptyDir": {} }),
+                ("tmpfs", _) => {
+                    json!({ "name": volume_name, "emptyDir": { "medium": "Memory" } })
+                }
+                _ => {
+                    warnings.push(format!(
+                        "services.{}.volumes: {} mount of {} has no Kubernetes equivalent, skipped",
+                        name,
+                        kind,
+                        source.unwrap_or(target)
+                    ));
+                    continue;
+                }
+            };
+            let mut mount = json!({ "name": volume_name, "mountPath": target });
+            if read_only {
+                mount["readOnly"] = json!(true);
+            }
+            mounts.push(mount);
+            volumes.push(definition);
+        }
+        for (section, dir) in [("configs", "/"), ("secrets", "/run/secrets/")] {
+            for entry in service[section].as_array().into_iter().flatten() {
+                let (source, target) = match entry {
+                    Value::String(source) => (source.as_str(), None),
+                    _ => match entry["source"].as_str() {
+                        Some(source) => (source, entry["target"].as_str()),
+                        None => continue,
+                    },
+                };
+                let Some((source_resource, key)) = sources.get(&(section, source.to_string()))
+                else {
+                    warnings.push(format!(
+                        "services.{}.{}: {} is not a file backed top-level entry, skipped",
+                        name, section, source
+                    ));
+                    continue;
+                };
+                let target = match target {
+                    Some(target) if target.starts_with('/') => target.to_string(),
+                    Some(target) => format!("{}{}", dir, target),
+                    None => format!("{}{}", dir, source),
+                };
+                let volume_name = format!("{}-{}", resource, volumes.len());
+                volumes.push(match section {
+                    "configs" => {
+                        json!({ "name": volume_name, "configMap": { "name": source_resource } })
+                    }
+                    _ => {
+                        json!({ "name": volume_name, "secret": { "secretName": source_resource } })
+                    }
+                });
+                mounts.push(json!({
+                    "name": volume_name,
+                    "mountPath": target,
+                    "subPath": key,
+                    "readOnly": true,
+                }));
+            }
+        }
+        if !mounts.is_empty() {
+            container["volumeMounts"] = json!(mounts);
+        }
+
+        if let Some(probe) = service.get("healthcheck").and_then(healthcheck_probe) {
+            container["livenessProbe"] = probe.clone();
+            container["readinessProbe"] = probe;
+        }
+
+        let mut security = Map::new();
+        if let Some(user) = service["user"].as_str() {
+            let ids: Vec<Option<u64>> = user.split(':').map(|id| id.parse().ok()).collect();
+            match ids.as_slice() {
+                [Some(uid)] => {
+                    security.insert("runAsUser".into(), json!(uid));
+                }
+                [Some(uid), Some(gid)] => {
+                    security.insert("runAsUser".into(), json!(uid));
+                    security.insert("runAsGroup".into(), json!(gid));
+                }
+                _ => warnings.push(format!(
+                    "services.{}.user: {} must be numeric for runAsUser, skipped",
+                    name, user
+                )),
+            }
+        }
+        let mut capabilities = Map::new();
+        if let Some(add) = service.get("cap_add") {
+            capabilities.insert("add".into(), add.clone());
+        }
+        if let Some(drop) = service.get("cap_drop") {
+            capabilities.insert("drop".into(), drop.clone());
+        }
+        if !capabilities.is_empty() {
+            security.insert("capabilities".into(), capabilities.into());
+        }
+        if let Some(read_only) = service["read_only"].as_bool() {
+            security.insert("readOnlyRootFilesystem".into(), json!(read_only));
+        }
+        if let Some(privileged) = service["privileged"].as_bool() {
+            security.insert("privileged".into(), json!(privileged));
+        }
+        if !security.is_empty() {
+            container["securityContext"] = security.into();
+        }
+
+        let deploy = &service["deploy"];
+        let mut resources = Map::new();
+        for (compose_key, key) in [("limits", "limits"), ("reservations", "requests")] {
+            let section = &deploy["resources"][compose_key];
+            let mut quantities = Map::new();
+            if let Some(cpus) = scalar(&section["cpus"]) {
+                quantities.insert("cpu".into(), json!(cpus));
+            }
+            if let Some(size) = section["memory"].as_str() {
+                quantities.insert("memory".into(), json!(memory(size)));
+            }
+            if !quantities.is_empty() {
+                resources.insert(key.into(), quantities.into());
+            }
+        }
+        if !resources.is_empty() {
+            container["resources"] = resources.into();
+        }
+
+        if let Some(restart) = service["restart"].as_str() {
+            if restart == "no" || restart.starts_with("on-failure") {
+                warnings.push(format!(
+                    "services.{}.restart: {} has no Deployment equivalent, pods are always restarted",
+                    name, restart
+                ));
+            }
+        }
+
+        // Kubernetes starts pods independently, so wait for dependencies to accept connections.
+        let dependencies: Vec<String> = match &service["depends_on"] {
+            Value::Array(names) => names
+                .iter()
+                .filter_map(|name| Some(name.as_str()?.to_string()))
+                .collect(),
+            Value::Object(names) => names.keys().cloned().collect(),
+            _ => vec![],
+        };
+        let mut init_containers = vec![];
+        for dependency in dependencies {
+            match service_ports.get(&dependency).and_then(|ports| ports.first()) {
+                Some((port, _, _)) => {
+                    let host = resource_name(&dependency);
+                    init_containers.push(json!({
+                        "name": format!("wait-for-{}", host),
+                        "image": "busybox:1.36",
+                        "command": [
+                            "sh",
+                            "-c",
+                            format!("until nc -z {} {}; do sleep 2; done", host, port),
+                        ],
+                    }));
+                }
+                None => warnings.push(format!(
+                    "services.{}.depends_on: {} has no port to wait on, Kubernetes starts pods independently",
+                    name, dependency
+                )),
+            }
+        }
+
+        let mut pod = json!({ "containers": [container] });
+        if !init_containers.is_empty() {
+            pod["initContainers"] = json!(init_containers);
+        }
+        if !volumes.is_empty() {
+            pod["volumes"] = json!(volumes);
+        }
+        manifests.push((
+            format!("{}-deployment.yaml", resource),
+            json!({
+                "apiVersion": "apps/v1",
+                "kind": "Deployment",
+                "metadata": { "name": resource, "labels": labels(&resource) },
+                "spec": {
+                    "replicas": deploy["replicas"].as_u64().unwrap_or(1),
+                    "selector": { "matchLabels": labels(&resource) },
+                    "template": {
+                        "metadata": { "labels": labels(&resource) },
+                        "spec": pod,
+                    },
+                },
+            }),
+        ));
+
+        if !ports.is_empty() {
+            let service_ports: Vec<Value> = ports
+                .iter()
+                .enumerate()
+                .map(|(index, (port, target, protocol))| {
+                    json!({
+                        "name": format!("{}-{}", protocol.to_lowercase(), index),
+                        "port": port,
+                        "targetPort": target,
+                        "protocol": protocol,
+                    })
+                })
+                .collect();
+            manifests.push((
+                format!("{}-service.yaml", resource),
+                json!({
+                    "apiVersion": "v1",
+                    "kind": "Service",
+                    "metadata": { "name": resource, "labels": labels(&resource) },
+                    "spec": { "selector": labels(&resource), "ports": service_ports },
+                }),
+            ));
+        }
+    }
+
+    for claim in claims {
+        manifests.push((
+            format!("{}-pvc.yaml", claim),
+            json!({
+                "apiVersion": "v1",
+                "kind": "PersistentVolumeClaim",
+                "metadata": { "name": claim },
+                "spec": {
+                    "accessModes": ["ReadWriteOnce"],
+                    "resources": { "requests": { "storage": "1Gi" } },
+                },
+            }),
+        ));
+    }
+
+    Ok(Conversion {
+        manifests: manifests
+            .into_iter()
+            .map(|(file, manifest)| (file, serde_yaml::to_string(&manifest).unwrap()))
+            .collect(),
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Converted manifest `file` of `compose`, parsed.
+    fn manifest(compose: &str, file: &str) -> Value {
+        let conversion = convert(compose).unwrap();
+        let (_, contents) = conversion
+            .manifests
+            .iter()
+            .find(|(name, _)| name == file)
+            .unwrap_or_else(|| panic!("no {} in {:?}", file, conversion.manifests));
+        serde_yaml::from_str(contents).unwrap()
+    }
+
+    fn pod(compose: &str, service: &str) -> Value {
+        manifest(compose, &format!("{}-deployment.yaml", service))["spec"]["template"]["spec"]
+            .clone()
+    }
+
+    #[test]
+    fn parses_port_strings() {
+        let mut warnings = vec![];
+        let service = json!({
+            "ports": ["127.0.0.1:8080:80/udp", "9000:9001", 3000, "8000-8010:8000-8010"],
+            "expose": ["5432"],
+        });
+        assert_eq!(
+            ports(&service, &mut warnings, "app"),
+            [
+                (8080, 80, "UDP".to_string()),
+                (9000, 9001, "TCP".to_string()),
+                (3000, 3000, "TCP".to_string()),
+                (5432, 5432, "TCP".to_string()),
+            ]
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("8000-8010"), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(seconds("1m30s"), Some(90));
+        assert_eq!(seconds("500ms"), Some(1));
+        assert_eq!(seconds("1h"), Some(3600));
+        assert_eq!(seconds("1.5s"), Some(2));
+        assert_eq!(seconds("30"), None);
+        assert_eq!(seconds("10d"), None);
+    }
+
+    #[test]
+    fn converts_named_bind_and_tmpfs_volumes() {
+        let compose = "\
+services:
+  db:
+    image: postgres:16.3
+    volumes:
+    - pgdata:/var/lib/postgresql/data
+    - ./init.sql:/docker-entrypoint-initdb.d/init.sql:ro
+    - type: tmpfs
+      target: /tmp
+volumes:
+  pgdata: {}
+";
+        let conversion = convert(compose).unwrap();
+        assert!(conversion
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("bind mount of ./init.sql")));
+        let pod = pod(compose, "db");
+        assert_eq!(
+            pod["volumes"],
+            json!([
+                { "name": "db-0", "persistentVolumeClaim": { "claimName": "pgdata" } },
+                { "name": "db-1", "emptyDir": { "medium": "Memory" } },
+            ])
+        );
+        assert_eq!(
+            pod["containers"][0]["volumeMounts"],
+            json!([
+                { "name": "db-0", "mountPath": "/var/lib/postgresql/data" },
+                { "name": "db-1", "mountPath": "/tmp" },
+            ])
+        );
+        assert_eq!(
+            manifest(compose, "pgdata-pvc.yaml")["kind"],
+            json!("PersistentVolumeClaim")
+        );
+    }
+
+    #[test]
+    fn waits_on_dependencies_with_init_containers() {
+        let compose = "\
+services:
+  app:
+    image: app:1.0
+    depends_on:
+      db:
+        condition: service_healthy
+      worker: {}
+  db:
+    image: postgres:16.3
+    expose:
+    - 5432
+  worker:
+    image: worker:1.0
+";
+        let conversion = convert(compose).unwrap();
+        assert!(conversion
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("worker has no port to wait on")));
+        assert_eq!(
+            pod(compose, "app")["initContainers"],
+            json!([{
+                "name": "wait-for-db",
+                "image": "busybox:1.36",
+                "command": ["sh", "-c", "until nc -z db 5432; do sleep 2; done"],
+            }])
+        );
+    }
+
+    #[test]
+    fn moves_secret_variables_to_a_secret() {
+        let compose = "\
+services:
+  app:
+    image: app:1.0
+    environment:
+      LOG_LEVEL: info
+      DATABASE_PASSWORD: hunter2
+";
+        assert_eq!(
+            manifest(compose, "app-configmap.yaml")["data"],
+            json!({ "LOG_LEVEL": "info" })
+        );
+        let secret = manifest(compose, "app-secret.yaml");
+        assert_eq!(secret["kind"], json!("Secret"));
+        assert_eq!(
+            secret["stringData"],
+            json!({ "DATABASE_PASSWORD": "hunter2" })
+        );
+        assert_eq!(
+            pod(compose, "app")["containers"][0]["envFrom"],
+            json!([
+                { "configMapRef": { "name": "app-env" } },
+                { "secretRef": { "name": "app-secret" } },
+            ])
+        );
+    }
+
+    #[test]
+    fn mounts_file_backed_configs_and_secrets() {
+        let compose = "\
+services:
+  app:
+    image: app:1.0
+    configs:
+    - source: app_config
+      target: /app/config.yaml
+    secrets:
+    - secret_key
+    env_file:
+    - path: .env.production
+      required: false
+configs:
+  app_config:
+    file: ./config.production.yaml
+secrets:
+  secret_key:
+    file: ./secrets/secret_key
+";
+        crate::utils::tests::in_temp_dir(|| {
+            utils::create_file("config.production.yaml", "app:\n  port: 8080\n");
+            std::fs::create_dir_all("secrets").unwrap();
+            utils::create_file("secrets/secret_key", "changeme");
+
+            let conversion = convert(compose).unwrap();
+            assert!(
+                !conversion
+                    .warnings
+                    .iter()
+                    .any(|warning| warning.contains("top-level")),
+                "{:?}",
+                conversion.warnings
+            );
+            assert_eq!(
+                manifest(compose, "app-config-config.yaml")["data"],
+                json!({ "config.production.yaml": "app:\n  port: 8080\n" })
+            );
+            assert_eq!(
+                manifest(compose, "secret-key-secret.yaml")["stringData"],
+                json!({ "secret_key": "changeme" })
+            );
+            let pod = pod(compose, "app");
+            assert_eq!(
+                pod["volumes"],
+                json!([
+                    { "name": "app-0", "configMap": { "name": "app-config" } },
+                    { "name": "app-1", "secret": { "secretName": "secret-key" } },
+                ])
+            );
+            let container = &pod["containers"][0];
+            assert_eq!(
+                container["volumeMounts"],
+                json!([
+                    {
+                        "name": "app-0",
+                        "mountPath": "/app/config.yaml",
+                        "subPath": "config.production.yaml",
+                        "readOnly": true,
+                    },
+                    {
+                        "name": "app-1",
+                        "mountPath": "/run/secrets/secret_key",
+                        "subPath": "secret_key",
+                        "readOnly": true,
+                    },
+                ])
+            );
+            assert_eq!(
+                container["envFrom"],
+                json!([{ "secretRef": { "name": "app-env-file", "optional": true } }])
+            );
+        });
+    }
+}
@@ -3,3 +3,51 @@ pub mod javascript;
 pub mod python;
 pub mod rust;
 pub mod typescript;
+
+/// Build secret `(id, source)` with the package registry credentials of `language`.
+pub fn registry_secret(language: &str) -> Option<(&'static str, &'static str)> {
+    match language {
+        "Typescript" => Some(typescript::REGISTRY_SECRET),
+        "Javascript" => Some(javascript::REGISTRY_SECRET),
+        "Python" => Some(python::REGISTRY_SECRET),
+        "Rust" => Some(rust::REGISTRY_SECRET),
+        "Golang" => Some(golang::REGISTRY_SECRET),
+        _ => None,
+    }
+}
+
+/// Files `init` scaffolds for `language`, as `(path, contents)`.
+pub fn scaffold(
+    language: &str,
+    name: &str,
+    health: Option<&crate::utils::Health>,
+    config: Option<&str>,
+) -> Vec<(&'static str, String)> {
+    match language {
+        "Typescript" => {
+            let (index, pkg, tsconfig) = typescript::scaffold(name, health, config);
+            vec![
+                ("src/index.ts", index),
+                ("package.json", pkg),
+                ("tsconfig.json", tsconfig),
+            ]
+        }
+        "Javascript" => {
+            let (index, pkg) = javascript::scaffold(name, health, config);
+            vec![("src/index.js", index), ("package.json", pkg)]
+        }
+        "Python" => {
+            let (index, requirements) = python::scaffold(health, config);
+            vec![("src/index.py", index), ("requirements.txt", requirements)]
+        }
+        "Rust" => {
+            let (main, cargo) = rust::scaffold(name, health, config);
+            vec![("src/main.rs", main), ("Cargo.toml", cargo)]
+        }
+        "Golang" => {
+            let (main, module) = golang::scaffold(name, health, config);
+            vec![("src/main.go", main), ("go.mod", module)]
+        }
+        _ => vec![],
+    }
+}
@@ -2,27 +2,96 @@ use crate::utils;
 use serde_json::json;
 use serde_yaml::to_string as to_yaml_string;
 
-pub fn dockerize(name: &str, config: Option<&str>) -> (String, String, String, String) {
+const TEST_COMMAND: &str = "go test ./...";
+const DEBUG_PORT: u16 = 2345;
+/// Build secret `(id, source)` with the package registry credentials.
+pub const REGISTRY_SECRET: (&str, &str) = ("git_credentials", "${HOME}/.git-credentials");
+
+pub fn dockerize(
+    name: &str,
+    options: &utils::Options,
+) -> (String, String, String, Vec<(String, String)>) {
+    let config = options.config;
+    let npm_cache = utils::cache_mounts(options.buildkit, &["/root/.npm"]);
+    let go_cache = utils::cache_mounts(options.buildkit, &["/go/pkg/mod", "/root/.cache/go-build"]);
+    let git_credentials = utils::secret_mount(
+        options.private_registry,
+        REGISTRY_SECRET.0,
+        "/root/.git-credentials",
+    );
+    let (packages, download) = if options.private_registry {
+        (
+            "RUN apk add --no-cache npm git\n".to_string(),
+            format!("ARG GOPRIVATE\nRUN {git_credentials}git config --global credential.helper store && go mod download all\n"),
+        )
+    } else {
+        (
+            "RUN apk add --no-cache npm\n".to_string(),
+            "RUN go mod download all\n".to_string(),
+        )
+    };
+    let nodemon = format!("RUN {npm_cache}npm install -g nodemon\n");
     let mut dockerfile = vec![
         "FROM golang:1.22-alpine as development\n",
-        "RUN apk add --update npm\n",
-        "RUN npm install -g nodemon\n",
+        &packages,
+        &nodemon,
         "WORKDIR /app",
         "COPY go.mod /app/go.mod",
-        "RUN go mod download\n",
+        &download,
         "COPY src /app/src",
         "COPY nodemon.json /app/nodemon.json\n",
         "# More development build configurations here\n",
         "FROM development as production\n",
         "# More production build configurations here",
     ];
-    let build = format!("RUN go build -o {name} /app/src/main.go\n");
+    let build = format!("RUN {go_cache}go build -o {name} /app/src/main.go\n");
     dockerfile.insert(9, &build);
 
-    let cmd: String;
-    if let Some(conf) = config {
-        cmd = format!("COPY config.{} /app/config.{}", conf, conf);
-        dockerfile.insert(5, &cmd);
+    if options.debug {
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("# More development"))
+            .unwrap();
+        dockerfile.insert(
+            position,
+            "RUN go install github.com/go-delve/delve/cmd/dlv@latest\n",
+        );
+    }
+
+    let test_stage: String;
+    if options.bake {
+        test_stage = format!("FROM development as test\n\nRUN {TEST_COMMAND}\n");
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("FROM development as production"))
+            .unwrap();
+        dockerfile.insert(position, &test_stage);
+    }
+
+    if options.host_user.is_some() {
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("# More development"))
+            .unwrap();
+        dockerfile.insert(position, utils::DEV_USER);
+    }
+
+    let labels = metadata(name).labels();
+    dockerfile.insert(dockerfile.len() - 1, &labels);
+
+    let health = options.health.as_ref().map(utils::Health::busybox_command);
+    let healthcheck: String;
+    if let Some(command) = &health {
+        healthcheck = utils::healthcheck_instruction(command);
+        dockerfile.insert(dockerfile.len() - 1, &healthcheck);
+    }
+
+    if options.hardened {
+        dockerfile.insert(dockerfile.len() - 1, utils::NON_ROOT_USER);
+    }
+
+    if options.buildkit || options.private_registry {
+        dockerfile.insert(0, utils::DOCKERFILE_SYNTAX);
     }
 
     let watch = match config {
@@ -34,71 +103,307 @@ pub fn dockerize(name: &str, config: Option<&str>) -> (String, String, String, S
         None => vec!["src".to_string()],
     };
 
+    let exec = if options.debug {
+        format!("dlv debug ./src/main.go --headless --listen=:{DEBUG_PORT} --api-version=2 --accept-multiclient --continue")
+    } else {
+        String::from("go run src/main.go")
+    };
+
     let nodemon_config = json!({
         "watch": watch,
         "ext": ".go",
         "ignore": [],
-        "exec": "go run src/main.go",
+        "exec": exec,
     });
 
+    let secrets = utils::registry_secrets(
+        options.private_registry,
+        REGISTRY_SECRET.0,
+        REGISTRY_SECRET.1,
+    );
+    let build_args = if options.private_registry {
+        std::collections::BTreeMap::from([("GOPRIVATE".to_string(), "${GOPRIVATE:-}".to_string())])
+    } else {
+        std::collections::BTreeMap::new()
+    };
+    let (app_secrets, app_secret_files) = utils::app_secrets(options.env_secrets);
+    let mut compose_secrets = secrets.clone();
+    compose_secrets.extend(app_secret_files);
+    let (configs, config_files) = utils::app_config(config, "development");
+
     let services = std::collections::HashMap::from([(
         "app".to_string(),
         utils::Service {
-            container_name: name.to_string(),
             build: utils::Build {
                 context: ".",
                 target: "development",
+                args: build_args.clone(),
+                secrets: secrets.keys().cloned().collect(),
             },
             command: String::from("nodemon"),
-            volumes: if config.is_some() {
-                vec![
-                    "./src:/app/src".into(),
-                    format!(
-                        "./config.{}:/app/config.{}",
-                        config.unwrap(),
-                        config.unwrap()
-                    ),
-                ]
+            ports: vec![],
+            configs,
+            secrets: app_secrets.clone(),
+            restart: "unless-stopped",
+            env_file: if options.env {
+                vec![utils::EnvFile::optional(".env")]
             } else {
-                vec!["./src:/app/src".into()]
+                vec![]
             },
-            restart: "unless-stopped",
+            deploy: None,
+            healthcheck: health.as_deref().map(utils::Healthcheck::new),
+            security: utils::Security::default(),
         },
     )]);
 
-    let compose_dev = utils::Compose {
-        version: "3.9",
+    let compose_dev = utils::Compose::new(
         services,
-    };
-
-    let services_prod = std::collections::HashMap::from([(
-        "app".to_string(),
-        utils::Service {
-            container_name: name.to_string(),
-            build: utils::Build {
-                context: ".",
-                target: "production",
-            },
-            command: format!("/app/target/release/{name}"),
-            volumes: vec![],
-            restart: "no",
-        },
-    )]);
+        compose_secrets.clone(),
+        config_files,
+        std::collections::BTreeMap::new(),
+    );
 
-    let compose_prod = utils::Compose {
-        version: "3.9",
-        services: services_prod,
-    };
+    let overlays = options
+        .environments
+        .iter()
+        .map(|environment| {
+            let (restart, deploy) = utils::environment(environment);
+            let (configs, config_files) = utils::app_config(config, environment);
+            let services = std::collections::HashMap::from([(
+                "app".to_string(),
+                utils::Service {
+                    build: utils::Build {
+                        context: ".",
+                        target: "production",
+                        args: build_args.clone(),
+                        secrets: secrets.keys().cloned().collect(),
+                    },
+                    command: format!("/app/target/release/{name}"),
+                    ports: vec![],
+                    configs,
+                    secrets: app_secrets.clone(),
+                    restart,
+                    env_file: vec![utils::EnvFile::optional(&format!(".env.{}", environment))],
+                    deploy: Some(deploy),
+                    healthcheck: health.as_deref().map(utils::Healthcheck::new),
+                    security: utils::Security::new(options.hardened),
+                },
+            )]);
+            let compose = utils::Compose::new(
+                services,
+                compose_secrets.clone(),
+                config_files,
+                std::collections::BTreeMap::new(),
+            );
+            (environment.clone(), to_yaml_string(&compose).unwrap())
+        })
+        .collect();
 
     (
         serde_json::to_string_pretty(&nodemon_config).unwrap(),
         dockerfile.join("\n"),
         to_yaml_string(&compose_dev).unwrap(),
-        to_yaml_string(&compose_prod).unwrap(),
+        overlays,
+    )
+}
+pub fn metadata(name: &str) -> utils::Metadata {
+    let module = utils::read_file("go.mod").and_then(|contents| {
+        contents
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("module "))
+            .map(|module| module.trim().to_string())
+    });
+    utils::Metadata {
+        title: module.clone().unwrap_or(name.to_string()),
+        source: module
+            .filter(|m| m.starts_with("github.com/") || m.starts_with("gitlab.com/"))
+            .map(|m| format!("https://{}", m)),
+        ..Default::default()
+    }
+    .with_git()
+}
+
+pub fn devcontainer(name: &str, options: &utils::Options) -> String {
+    let mut ports: Vec<u16> = options.health.iter().filter_map(|h| h.port()).collect();
+    if options.debug {
+        ports.push(DEBUG_PORT);
+    }
+
+    utils::devcontainer(
+        name,
+        options.debug,
+        &["golang.go"],
+        &ports,
+        "go mod download",
     )
 }
-pub fn scaffold(name: &str) -> (String, String) {
-    let main = r#"package main
+pub fn dev_compose(options: &utils::Options) -> String {
+    utils::dev_compose(options.host_user, &[("go-mod", "/go/pkg/mod")])
+}
+pub fn debug_compose() -> String {
+    utils::debug_compose(DEBUG_PORT, true, &[])
+}
+pub fn launch() -> String {
+    let launch = json!({
+        "version": "0.2.0",
+        "configurations": [{
+            "name": "Attach to Docker",
+            "type": "go",
+            "request": "attach",
+            "mode": "remote",
+            "host": "127.0.0.1",
+            "port": DEBUG_PORT,
+            "substitutePath": [{
+                "from": "${workspaceFolder}",
+                "to": "/app",
+            }],
+        }],
+    });
+
+    serde_json::to_string_pretty(&launch).unwrap()
+}
+pub fn scaffold(
+    name: &str,
+    health: Option<&utils::Health>,
+    config: Option<&str>,
+) -> (String, String) {
+    let (mut imports, mut modules, mut requires) = (vec!["fmt"], vec![], String::new());
+    let (loader, load) = match config {
+        Some(format) => {
+            let (read, module) = match format {
+                "json" => (
+                    r#"data, err := os.ReadFile(path)
+	if err != nil {
+		log.Fatalf("failed to read config file: %v", err)
+	}
+	if err := json.Unmarshal(data, &config); err != nil {
+		log.Fatalf("failed to parse config file: %v", err)
+	}"#,
+                    None,
+                ),
+                "yaml" => (
+                    r#"data, err := os.ReadFile(path)
+	if err != nil {
+		log.Fatalf("failed to read config file: %v", err)
+	}
+	if err := yaml.Unmarshal(data, &config); err != nil {
+		log.Fatalf("failed to parse config file: %v", err)
+	}"#,
+                    Some(("gopkg.in/yaml.v3", "v3.0.1")),
+                ),
+                "ini" => (
+                    r#"file, err := ini.Load(path)
+	if err != nil {
+		log.Fatalf("failed to read config file: %v", err)
+	}
+	if err := file.Section("app").MapTo(&config.App); err != nil {
+		log.Fatalf("failed to parse config file: %v", err)
+	}"#,
+                    Some(("gopkg.in/ini.v1", "v1.67.0")),
+                ),
+                _ => (
+                    r#"if _, err := toml.DecodeFile(path, &config); err != nil {
+		log.Fatalf("failed to load config file: %v", err)
+	}"#,
+                    Some(("github.com/BurntSushi/toml", "v1.3.2")),
+                ),
+            };
+            imports.extend(["log", "os"]);
+            match module {
+                Some((module, version)) => {
+                    modules.push(module);
+                    requires = format!("\n\nrequire {module} {version}\n");
+                }
+                None => imports.push("encoding/json"),
+            }
+            (
+                format!(
+                    r#"
+type Config struct {{
+	App AppConfig `{format}:"app"`
+}}
+
+type AppConfig struct {{
+	Port        int    `{format}:"port"`
+	LogLevel    string `{format}:"log_level"`
+	DatabaseURL string `{format}:"database_url"`
+}}
+
+func loadConfig() Config {{
+	path := os.Getenv("CONFIG_PATH")
+	if path == "" {{
+		path = "config.{format}"
+	}}
+	var config Config
+	{read}
+	return config
+}}
+"#
+                ),
+                r#"	config := loadConfig()
+	fmt.Printf("Loaded config: port=%d log_level=%s database_url=%s\n", config.App.Port, config.App.LogLevel, config.App.DatabaseURL)
+"#,
+            )
+        }
+        None => (String::new(), ""),
+    };
+
+    let port = health.and_then(|h| h.port());
+    if port.is_some() {
+        imports.extend(["net/http", "os"]);
+    }
+    imports.sort();
+    imports.dedup();
+    let mut imports = imports
+        .iter()
+        .map(|import| format!("\t\"{}\"\n", import))
+        .collect::<String>();
+    if !modules.is_empty() {
+        imports.push('\n');
+        imports.extend(modules.iter().map(|module| format!("\t\"{}\"\n", module)));
+    }
+
+    let main = match port {
+        Some(port) => format!(
+            r#"package main
+
+import (
+{imports})
+{loader}
+func main() {{
+{load}	port := os.Getenv("PORT")
+	if port == "" {{
+		port = {default}
+	}}
+	http.HandleFunc("{path}", func(w http.ResponseWriter, r *http.Request) {{
+		fmt.Fprint(w, "ok")
+	}})
+	http.HandleFunc("/", func(w http.ResponseWriter, r *http.Request) {{
+		fmt.Fprint(w, "Hello from Golang!")
+	}})
+	fmt.Println("Listening on port " + port)
+	http.ListenAndServe(":"+port, nil)
+}}
+"#,
+            default = if config.is_some() {
+                "fmt.Sprint(config.App.Port)".to_string()
+            } else {
+                format!("\"{port}\"")
+            },
+            path = health.and_then(|h| h.path()).unwrap_or("/health"),
+        ),
+        None if config.is_some() => format!(
+            r#"package main
+
+import (
+{imports})
+{loader}
+func main() {{
+{load}	fmt.Println("Hello from Golang!")
+}}
+"#
+        ),
+        None => r#"package main
     
     import "fmt"
     
@@ -106,12 +411,13 @@ pub fn scaffold(name: &str) -> (String, String) {
         fmt.Println("Hello from Golang!")
     }
     "#
-    .to_string();
+        .to_string(),
+    };
 
     let module = format!(
         r#"module {name}/main
         
-go 1.22"#
+go 1.22{requires}"#
     )
     .to_string();
 
@@ -2,14 +2,29 @@ use crate::utils;
 use serde_json::json;
 use serde_yaml::to_string as to_yaml_string;
 
-pub fn dockerize(name: &str, config: Option<&str>) -> (String, String, String, String) {
+const TEST_COMMAND: &str = "python -m unittest discover -s src || [ $? -eq 5 ]";
+const DEBUG_PORT: u16 = 5678;
+/// Build secret `(id, source)` with the package registry credentials.
+pub const REGISTRY_SECRET: (&str, &str) = ("pipconf", "${HOME}/.config/pip/pip.conf");
+
+pub fn dockerize(
+    name: &str,
+    options: &utils::Options,
+) -> (String, String, String, Vec<(String, String)>) {
+    let config = options.config;
+    let npm_cache = utils::cache_mounts(options.buildkit, &["/root/.npm"]);
+    let pip_cache = utils::cache_mounts(options.buildkit, &["/root/.cache/pip"]);
+    let pip_conf =
+        utils::secret_mount(options.private_registry, REGISTRY_SECRET.0, "/etc/pip.conf");
+    let nodemon = format!("RUN {npm_cache}npm install -g nodemon\n");
+    let install = format!("RUN {pip_cache}{pip_conf}pip install -r requirements.txt\n");
     let mut dockerfile = vec![
-        "FROM python:3-alpine as development\n",
-        "RUN apk add --update npm\n",
-        "RUN npm install -g nodemon\n",
+        "FROM python:3.12-alpine as development\n",
+        "RUN apk add --no-cache npm\n",
+        &nodemon,
         "WORKDIR /app",
         "COPY requirements.txt /app/requirements.txt",
-        "RUN pip install -r requirements.txt\n",
+        &install,
         "COPY src /app/src",
         "COPY nodemon.json /app/nodemon.json\n",
         "# More development build configurations here\n",
@@ -17,10 +32,48 @@ pub fn dockerize(name: &str, config: Option<&str>) -> (String, String, String, S
         "# More production build configurations here",
     ];
 
-    let cmd: String;
-    if let Some(conf) = config {
-        cmd = format!("COPY config.{} /app/config.{}", conf, conf);
-        dockerfile.insert(5, &cmd);
+    if options.debug {
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("# More development"))
+            .unwrap();
+        dockerfile.insert(position, "RUN pip install debugpy\n");
+    }
+
+    let test_stage: String;
+    if options.bake {
+        test_stage = format!("FROM development as test\n\nRUN {TEST_COMMAND}\n");
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("FROM development as production"))
+            .unwrap();
+        dockerfile.insert(position, &test_stage);
+    }
+
+    if options.host_user.is_some() {
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("# More development"))
+            .unwrap();
+        dockerfile.insert(position, utils::DEV_USER);
+    }
+
+    let labels = metadata(name).labels();
+    dockerfile.insert(dockerfile.len() - 1, &labels);
+
+    let health = options.health.as_ref().map(health_command);
+    let healthcheck: String;
+    if let Some(command) = &health {
+        healthcheck = utils::healthcheck_instruction(command);
+        dockerfile.insert(dockerfile.len() - 1, &healthcheck);
+    }
+
+    if options.hardened {
+        dockerfile.insert(dockerfile.len() - 1, utils::NON_ROOT_USER);
+    }
+
+    if options.buildkit || options.private_registry {
+        dockerfile.insert(0, utils::DOCKERFILE_SYNTAX);
     }
 
     let watch = match config {
@@ -32,69 +85,298 @@ pub fn dockerize(name: &str, config: Option<&str>) -> (String, String, String, S
         None => vec!["src".to_string()],
     };
 
+    let exec = if options.debug {
+        format!("python3 -m debugpy --listen 0.0.0.0:{DEBUG_PORT} ./src/index.py")
+    } else {
+        String::from("python3 ./src/index.py")
+    };
+
     let nodemon_config = json!({
         "watch": watch,
         "ext": ".py",
         "ignore": [],
-        "exec": "python3 ./src/index.py",
+        "exec": exec,
     });
 
+    let secrets = utils::registry_secrets(
+        options.private_registry,
+        REGISTRY_SECRET.0,
+        REGISTRY_SECRET.1,
+    );
+    let build_args = std::collections::BTreeMap::new();
+    let (app_secrets, app_secret_files) = utils::app_secrets(options.env_secrets);
+    let mut compose_secrets = secrets.clone();
+    compose_secrets.extend(app_secret_files);
+    let (configs, config_files) = utils::app_config(config, "development");
+
     let services = std::collections::HashMap::from([(
         "app".to_string(),
         utils::Service {
-            container_name: name.to_string(),
             build: utils::Build {
                 context: ".",
                 target: "development",
+                args: build_args.clone(),
+                secrets: secrets.keys().cloned().collect(),
             },
             command: String::from("nodemon"),
-            volumes: if config.is_some() {
-                vec![
-                    "./src:/app/src".into(),
-                    format!(
-                        "./config.{}:/app/config.{}",
-                        config.unwrap(),
-                        config.unwrap()
-                    ),
-                ]
+            ports: vec![],
+            configs,
+            secrets: app_secrets.clone(),
+            restart: "unless-stopped",
+            env_file: if options.env {
+                vec![utils::EnvFile::optional(".env")]
             } else {
-                vec!["./src:/app/src".into()]
+                vec![]
             },
-            restart: "unless-stopped",
+            deploy: None,
+            healthcheck: health.as_deref().map(utils::Healthcheck::new),
+            security: utils::Security::default(),
         },
     )]);
 
-    let compose_dev = utils::Compose {
-        version: "3.9",
+    let compose_dev = utils::Compose::new(
         services,
-    };
-
-    let services_prod = std::collections::HashMap::from([(
-        "app".to_string(),
-        utils::Service {
-            container_name: name.to_string(),
-            build: utils::Build {
-                context: ".",
-                target: "production",
-            },
-            command: String::from("python3 /app/src/index.py"),
-            volumes: vec![],
-            restart: "no",
-        },
-    )]);
+        compose_secrets.clone(),
+        config_files,
+        std::collections::BTreeMap::new(),
+    );
 
-    let compose_prod = utils::Compose {
-        version: "3.9",
-        services: services_prod,
-    };
+    let overlays = options
+        .environments
+        .iter()
+        .map(|environment| {
+            let (restart, deploy) = utils::environment(environment);
+            let (configs, config_files) = utils::app_config(config, environment);
+            let services = std::collections::HashMap::from([(
+                "app".to_string(),
+                utils::Service {
+                    build: utils::Build {
+                        context: ".",
+                        target: "production",
+                        args: build_args.clone(),
+                        secrets: secrets.keys().cloned().collect(),
+                    },
+                    command: String::from("python3 /app/src/index.py"),
+                    ports: vec![],
+                    configs,
+                    secrets: app_secrets.clone(),
+                    restart,
+                    env_file: vec![utils::EnvFile::optional(&format!(".env.{}", environment))],
+                    deploy: Some(deploy),
+                    healthcheck: health.as_deref().map(utils::Healthcheck::new),
+                    security: utils::Security::new(options.hardened),
+                },
+            )]);
+            let compose = utils::Compose::new(
+                services,
+                compose_secrets.clone(),
+                config_files,
+                std::collections::BTreeMap::new(),
+            );
+            (environment.clone(), to_yaml_string(&compose).unwrap())
+        })
+        .collect();
 
     (
         serde_json::to_string_pretty(&nodemon_config).unwrap(),
         dockerfile.join("\n"),
         to_yaml_string(&compose_dev).unwrap(),
-        to_yaml_string(&compose_prod).unwrap(),
+        overlays,
     )
 }
-pub fn scaffold() -> String {
-    "print('Hello from Python!')".to_string()
+pub fn metadata(name: &str) -> utils::Metadata {
+    let pyproject: toml::Value = utils::read_file("pyproject.toml")
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or(toml::Value::Table(Default::default()));
+    let project = pyproject.get("project");
+    let field = |key: &str| {
+        project
+            .and_then(|p| p.get(key))
+            .and_then(|v| v.as_str())
+            .map(String::from)
+    };
+    let urls = project.and_then(|p| p.get("urls"));
+    utils::Metadata {
+        title: field("name").unwrap_or(name.to_string()),
+        version: field("version"),
+        description: field("description"),
+        license: field("license").or(project
+            .and_then(|p| p.get("license"))
+            .and_then(|l| l.get("text"))
+            .and_then(|t| t.as_str())
+            .map(String::from)),
+        source: ["Repository", "Source", "repository", "source"]
+            .iter()
+            .find_map(|key| urls.and_then(|u| u.get(key)).and_then(|u| u.as_str()))
+            .map(String::from),
+    }
+    .with_git()
+}
+
+pub fn devcontainer(name: &str, options: &utils::Options) -> String {
+    let mut ports: Vec<u16> = options.health.iter().filter_map(|h| h.port()).collect();
+    if options.debug {
+        ports.push(DEBUG_PORT);
+    }
+
+    utils::devcontainer(
+        name,
+        options.debug,
+        &["ms-python.python", "ms-python.debugpy"],
+        &ports,
+        "pip install -r requirements.txt",
+    )
+}
+pub fn dev_compose(options: &utils::Options) -> String {
+    utils::dev_compose(options.host_user, &[])
+}
+pub fn debug_compose() -> String {
+    utils::debug_compose(DEBUG_PORT, false, &[])
+}
+pub fn launch() -> String {
+    let launch = json!({
+        "version": "0.2.0",
+        "configurations": [{
+            "name": "Attach to Docker",
+            "type": "debugpy",
+            "request": "attach",
+            "connect": {
+                "host": "localhost",
+                "port": DEBUG_PORT,
+            },
+            "pathMappings": [{
+                "localRoot": "${workspaceFolder}",
+                "remoteRoot": "/app",
+            }],
+        }],
+    });
+
+    serde_json::to_string_pretty(&launch).unwrap()
+}
+pub fn health_command(health: &utils::Health) -> String {
+    match health {
+        utils::Health::Http { port, path } => format!(
+            "python3 -c \"import urllib.request; urllib.request.urlopen('http://localhost:{}{}', timeout=3)\"",
+            port, path
+        ),
+        utils::Health::Tcp { port } => format!(
+            "python3 -c \"import socket; socket.create_connection(('localhost', {}), timeout=3)\"",
+            port
+        ),
+        utils::Health::Command(command) => command.clone(),
+    }
+}
+
+pub fn scaffold(health: Option<&utils::Health>, config: Option<&str>) -> (String, String) {
+    let port = health.and_then(|h| h.port());
+    let mut imports = vec![];
+    if port.is_some() || config.is_some() {
+        imports.push("import os".to_string());
+    }
+    if port.is_some() {
+        imports.push("from http.server import BaseHTTPRequestHandler, HTTPServer".to_string());
+    }
+    let (loader, load, requirements) = match config {
+        Some(format) => {
+            let (module, read) = match format {
+                "json" => (
+                    "json",
+                    "with open(path) as file:\n        app = json.load(file)[\"app\"]",
+                ),
+                "yaml" => (
+                    "yaml",
+                    "with open(path) as file:\n        app = yaml.safe_load(file)[\"app\"]",
+                ),
+                "ini" => (
+                    "configparser",
+                    "parser = configparser.ConfigParser()\n    parser.read(path)\n    app = parser[\"app\"]",
+                ),
+                _ => (
+                    "tomllib",
+                    "with open(path, \"rb\") as file:\n        app = tomllib.load(file)[\"app\"]",
+                ),
+            };
+            if module != "yaml" {
+                imports.push(format!("import {}", module));
+            }
+            imports.push("from dataclasses import dataclass".to_string());
+            (
+                format!(
+                    r#"
+
+@dataclass
+class Settings:
+    port: int
+    log_level: str
+    database_url: str
+
+
+def load_settings() -> Settings:
+    path = os.environ.get("CONFIG_PATH", "config.{format}")
+    {read}
+    return Settings(
+        port=int(app["port"]),
+        log_level=app["log_level"],
+        database_url=app["database_url"],
+    )
+"#
+                ),
+                "settings = load_settings()\nprint(f\"Loaded config: {settings}\")\n",
+                if format == "yaml" { "PyYAML\n" } else { "" },
+            )
+        }
+        None => (String::new(), "", ""),
+    };
+    // Plain imports before `from` imports, sorted by module name like isort would.
+    imports.sort_by_key(|import| {
+        (
+            import.starts_with("from"),
+            import
+                .split_whitespace()
+                .nth(1)
+                .unwrap_or_default()
+                .to_string(),
+        )
+    });
+    let mut imports = imports.join("\n");
+    if config == Some("yaml") {
+        imports.push_str("\n\nimport yaml");
+    }
+
+    let index = match port {
+        Some(port) => format!(
+            r#"{imports}
+{loader}
+
+class Handler(BaseHTTPRequestHandler):
+    def do_GET(self):
+        body = b"ok" if self.path == "{path}" else b"Hello from Python!"
+        self.send_response(200)
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+
+{load}port = int(os.environ.get("PORT", {default}))
+print(f"Listening on port {{port}}")
+HTTPServer(("0.0.0.0", port), Handler).serve_forever()
+"#,
+            default = if config.is_some() {
+                "settings.port".to_string()
+            } else {
+                port.to_string()
+            },
+            path = health.and_then(|h| h.path()).unwrap_or("/health"),
+        ),
+        None if config.is_some() => format!(
+            r#"{imports}
+{loader}
+
+{load}print("Hello from Python!")
+"#
+        ),
+        None => "print('Hello from Python!')".to_string(),
+    };
+
+    (index, requirements.to_string())
 }
@@ -2,26 +2,86 @@ use crate::utils;
 use serde_json::json;
 use serde_yaml::to_string as to_yaml_string;
 
-pub fn dockerize(name: &str, config: Option<&str>) -> (String, String, String, String) {
+const TEST_COMMAND: &str = "cargo test";
+const DEBUG_PORT: u16 = 2345;
+/// Build secret `(id, source)` with the package registry credentials.
+pub const REGISTRY_SECRET: (&str, &str) = ("cargo_credentials", "${HOME}/.cargo/credentials.toml");
+
+pub fn dockerize(
+    name: &str,
+    options: &utils::Options,
+) -> (String, String, String, Vec<(String, String)>) {
+    let config = options.config;
+    let npm_cache = utils::cache_mounts(options.buildkit, &["/root/.npm"]);
+    let cargo_cache = utils::cache_mounts(
+        options.buildkit,
+        &["/usr/local/cargo/registry", "/usr/local/cargo/git"],
+    );
+    let credentials = utils::secret_mount(
+        options.private_registry,
+        REGISTRY_SECRET.0,
+        "/usr/local/cargo/credentials.toml",
+    );
+    let nodemon = format!("RUN {npm_cache}npm install -g nodemon\n");
+    let build = format!("RUN {credentials}cargo build\n");
+    let release = format!("RUN {cargo_cache}{credentials}cargo build --release\n");
     let mut dockerfile = vec![
         "FROM rust:1.76-alpine as development\n",
-        "RUN apk add --update npm\n",
-        "RUN npm install -g nodemon\n",
+        "RUN apk add --no-cache npm\n",
+        &nodemon,
         "WORKDIR /app",
         "COPY Cargo.toml /app/Cargo.toml",
         "COPY src /app/src",
-        "RUN cargo build\n",
+        &build,
         "COPY nodemon.json /app/nodemon.json\n",
         "# More development build configurations here\n",
         "FROM development as production\n",
-        "RUN cargo build --release\n",
+        &release,
         "# More production build configurations here",
     ];
 
-    let cmd: String;
-    if let Some(conf) = config {
-        cmd = format!("COPY config.{} /app/config.{}", conf, conf);
-        dockerfile.insert(5, &cmd);
+    if options.debug {
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("# More development"))
+            .unwrap();
+        dockerfile.insert(position, "RUN apk add --no-cache gdb\n");
+    }
+
+    let test_stage: String;
+    if options.bake {
+        test_stage = format!("FROM development as test\n\nRUN {TEST_COMMAND}\n");
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("FROM development as production"))
+            .unwrap();
+        dockerfile.insert(position, &test_stage);
+    }
+
+    if options.host_user.is_some() {
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("# More development"))
+            .unwrap();
+        dockerfile.insert(position, utils::DEV_USER);
+    }
+
+    let labels = metadata(name).labels();
+    dockerfile.insert(dockerfile.len() - 1, &labels);
+
+    let health = options.health.as_ref().map(utils::Health::busybox_command);
+    let healthcheck: String;
+    if let Some(command) = &health {
+        healthcheck = utils::healthcheck_instruction(command);
+        dockerfile.insert(dockerfile.len() - 1, &healthcheck);
+    }
+
+    if options.hardened {
+        dockerfile.insert(dockerfile.len() - 1, utils::NON_ROOT_USER);
+    }
+
+    if options.buildkit || options.private_registry {
+        dockerfile.insert(0, utils::DOCKERFILE_SYNTAX);
     }
 
     let watch = match config {
@@ -40,69 +100,276 @@ pub fn dockerize(name: &str, config: Option<&str>) -> (String, String, String, S
         "exec": "cargo run",
     });
 
+    let secrets = utils::registry_secrets(
+        options.private_registry,
+        REGISTRY_SECRET.0,
+        REGISTRY_SECRET.1,
+    );
+    let build_args = std::collections::BTreeMap::new();
+    let (app_secrets, app_secret_files) = utils::app_secrets(options.env_secrets);
+    let mut compose_secrets = secrets.clone();
+    compose_secrets.extend(app_secret_files);
+    let (configs, config_files) = utils::app_config(config, "development");
+
     let services = std::collections::HashMap::from([(
         "app".to_string(),
         utils::Service {
-            container_name: name.to_string(),
             build: utils::Build {
                 context: ".",
                 target: "development",
+                args: build_args.clone(),
+                secrets: secrets.keys().cloned().collect(),
             },
             command: String::from("nodemon"),
-            volumes: if config.is_some() {
-                vec![
-                    "./src:/app/src".into(),
-                    format!(
-                        "./config.{}:/app/config.{}",
-                        config.unwrap(),
-                        config.unwrap()
-                    ),
-                ]
+            ports: vec![],
+            configs,
+            secrets: app_secrets.clone(),
+            restart: "unless-stopped",
+            env_file: if options.env {
+                vec![utils::EnvFile::optional(".env")]
             } else {
-                vec!["./src:/app/src".into()]
+                vec![]
             },
-            restart: "unless-stopped",
+            deploy: None,
+            healthcheck: health.as_deref().map(utils::Healthcheck::new),
+            security: utils::Security::default(),
         },
     )]);
 
-    let compose_dev = utils::Compose {
-        version: "3.9",
+    let compose_dev = utils::Compose::new(
         services,
-    };
+        compose_secrets.clone(),
+        config_files,
+        std::collections::BTreeMap::new(),
+    );
 
-    let services_prod = std::collections::HashMap::from([(
-        "app".to_string(),
-        utils::Service {
-            container_name: name.to_string(),
-            build: utils::Build {
-                context: ".",
-                target: "production",
-            },
-            command: format!("./app/target/release/{name}"),
-            volumes: vec![],
-            restart: "no",
-        },
-    )]);
-
-    let compose_prod = utils::Compose {
-        version: "3.9",
-        services: services_prod,
-    };
+    let overlays = options
+        .environments
+        .iter()
+        .map(|environment| {
+            let (restart, deploy) = utils::environment(environment);
+            let (configs, config_files) = utils::app_config(config, environment);
+            let services = std::collections::HashMap::from([(
+                "app".to_string(),
+                utils::Service {
+                    build: utils::Build {
+                        context: ".",
+                        target: "production",
+                        args: build_args.clone(),
+                        secrets: secrets.keys().cloned().collect(),
+                    },
+                    command: format!("./app/target/release/{name}"),
+                    ports: vec![],
+                    configs,
+                    secrets: app_secrets.clone(),
+                    restart,
+                    env_file: vec![utils::EnvFile::optional(&format!(".env.{}", environment))],
+                    deploy: Some(deploy),
+                    healthcheck: health.as_deref().map(utils::Healthcheck::new),
+                    security: utils::Security::new(options.hardened),
+                },
+            )]);
+            let compose = utils::Compose::new(
+                services,
+                compose_secrets.clone(),
+                config_files,
+                std::collections::BTreeMap::new(),
+            );
+            (environment.clone(), to_yaml_string(&compose).unwrap())
+        })
+        .collect();
 
     (
         serde_json::to_string_pretty(&nodemon_config).unwrap(),
         dockerfile.join("\n"),
         to_yaml_string(&compose_dev).unwrap(),
-        to_yaml_string(&compose_prod).unwrap(),
+        overlays,
     )
 }
-pub fn scaffold(name: &str) -> (String, String) {
-    let main = r#"
+pub fn metadata(name: &str) -> utils::Metadata {
+    let cargo: toml::Value = utils::read_file("Cargo.toml")
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or(toml::Value::Table(Default::default()));
+    let field = |key: &str| {
+        cargo
+            .get("package")
+            .and_then(|p| p.get(key))
+            .and_then(|v| v.as_str())
+            .map(String::from)
+    };
+    utils::Metadata {
+        title: field("name").unwrap_or(name.to_string()),
+        version: field("version"),
+        description: field("description"),
+        license: field("license"),
+        source: field("repository"),
+    }
+    .with_git()
+}
+
+pub fn devcontainer(name: &str, options: &utils::Options) -> String {
+    let mut ports: Vec<u16> = options.health.iter().filter_map(|h| h.port()).collect();
+    if options.debug {
+        ports.push(DEBUG_PORT);
+    }
+
+    utils::devcontainer(
+        name,
+        options.debug,
+        &["rust-lang.rust-analyzer", "ms-vscode.cpptools"],
+        &ports,
+        "cargo fetch",
+    )
+}
+pub fn dev_compose(options: &utils::Options) -> String {
+    utils::dev_compose(
+        options.host_user,
+        &[
+            ("cargo-target", "/app/target"),
+            ("cargo-registry", "/usr/local/cargo/registry"),
+        ],
+    )
+}
+/// gdbserver halts the binary until a debugger attaches, so it only runs with the debug overlay.
+pub fn debug_compose(name: &str) -> String {
+    utils::debug_compose(
+        DEBUG_PORT,
+        true,
+        &[
+            "nodemon",
+            "--exec",
+            &format!("cargo build && gdbserver 0.0.0.0:{DEBUG_PORT} target/debug/{name}"),
+        ],
+    )
+}
+pub fn launch(name: &str) -> String {
+    let launch = json!({
+        "version": "0.2.0",
+        "configurations": [{
+            "name": "Launch against gdbserver in Docker",
+            "type": "cppdbg",
+            "request": "launch",
+            // gdb runs inside the container, where the binary lives in the cargo-target volume.
+            "program": format!("/app/target/debug/{name}"),
+            "cwd": "/app",
+            "MIMode": "gdb",
+            "miDebuggerServerAddress": format!("localhost:{DEBUG_PORT}"),
+            "pipeTransport": {
+                "pipeCwd": "${workspaceFolder}",
+                "pipeProgram": "docker",
+                "pipeArgs": ["compose", "exec", "-T", "app", "sh", "-c"],
+                "debuggerPath": "/usr/bin/gdb",
+            },
+            "sourceFileMap": {
+                "/app": "${workspaceFolder}",
+            },
+        }],
+    });
+
+    serde_json::to_string_pretty(&launch).unwrap()
+}
+pub fn scaffold(
+    name: &str,
+    health: Option<&utils::Health>,
+    config: Option<&str>,
+) -> (String, String) {
+    let (loader, load, dependency) = match config {
+        Some(format) => {
+            let (parse, dependency) = match format {
+                "json" => ("serde_json::from_str", "serde_json = \"1\""),
+                "yaml" => ("serde_yaml::from_str", "serde_yaml = \"0.9\""),
+                "ini" => ("serde_ini::from_str", "serde_ini = \"0.2\""),
+                _ => ("toml::from_str", "toml = \"0.8\""),
+            };
+            (
+                format!(
+                    r#"use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Config {{
+    app: AppConfig,
+}}
+
+#[derive(Deserialize)]
+struct AppConfig {{
+    port: u16,
+    log_level: String,
+    database_url: String,
+}}
+
+fn load_config() -> Config {{
+    let path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.{format}".to_string());
+    let contents = std::fs::read_to_string(&path).expect("failed to read config file");
+    {parse}(&contents).expect("failed to parse config file")
+}}
+"#
+                ),
+                r#"    let config = load_config();
+    println!(
+        "Loaded config: port={} log_level={} database_url={}",
+        config.app.port, config.app.log_level, config.app.database_url
+    );
+"#,
+                format!(
+                    "serde = {{ version = \"1\", features = [\"derive\"] }}\n    {dependency}\n    "
+                ),
+            )
+        }
+        None => (String::new(), "", String::new()),
+    };
+
+    let main = match health.and_then(|h| h.port()) {
+        Some(port) => format!(
+            r#"use std::io::{{Read, Write}};
+use std::net::TcpListener;
+{loader}
+fn main() {{
+{load}    let port = std::env::var("PORT").unwrap_or_else(|_| {default}.to_string());
+    let listener = TcpListener::bind(format!("0.0.0.0:{{}}", port)).unwrap();
+    println!("Listening on port {{}}", port);
+    for stream in listener.incoming() {{
+        let mut stream = match stream {{
+            Ok(stream) => stream,
+            Err(_) => continue,
+        }};
+        let mut buffer = [0; 1024];
+        let read = stream.read(&mut buffer).unwrap_or(0);
+        let request = String::from_utf8_lossy(&buffer[..read]);
+        let body = if request.starts_with("GET {path} ") {{
+            "ok"
+        }} else {{
+            "Hello from Rust!"
+        }};
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {{}}\r\n\r\n{{}}",
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes());
+    }}
+}}
+"#,
+            default = if config.is_some() {
+                "config.app.port".to_string()
+            } else {
+                format!("\"{port}\"")
+            },
+            path = health.and_then(|h| h.path()).unwrap_or("/health"),
+        ),
+        None if config.is_some() => format!(
+            r#"{loader}
+fn main() {{
+{load}    println!("Hello from Rust!");
+}}
+"#
+        ),
+        None => r#"
     fn main(){
         println!("Hello from Rust!")
     }
     "#
-    .to_string();
+        .to_string(),
+    };
 
     let cargo = format!(
         r#"[package]
@@ -113,7 +380,7 @@ pub fn scaffold(name: &str) -> (String, String) {
     # See more keys and their definitions at https://doc.rust-lang.org/cargo/reference/manifest.html
     
     [dependencies]
-    "#
+    {dependency}"#
     )
     .to_string();
 
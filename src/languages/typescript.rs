@@ -2,13 +2,27 @@ use crate::utils;
 use serde_json::json;
 use serde_yaml::to_string as to_yaml_string;
 
-pub fn dockerize(name: &str, config: Option<&str>) -> (String, String, String, String) {
+const TEST_COMMAND: &str = "npm test --if-present";
+const DEBUG_PORT: u16 = 9229;
+/// Build secret `(id, source)` with the package registry credentials.
+pub const REGISTRY_SECRET: (&str, &str) = ("npmrc", "${HOME}/.npmrc");
+
+pub fn dockerize(
+    name: &str,
+    options: &utils::Options,
+) -> (String, String, String, Vec<(String, String)>) {
+    let config = options.config;
+    let npm_cache = utils::cache_mounts(options.buildkit, &["/root/.npm"]);
+    let npmrc = utils::secret_mount(options.private_registry, REGISTRY_SECRET.0, "/root/.npmrc");
+    let nodemon = format!("RUN {npm_cache}npm install -g nodemon ts-node\n");
+    let install = format!("RUN {npm_cache}{npmrc}npm install\n");
+    let install_prod = format!("RUN {npm_cache}{npmrc}npm install --omit=dev\n");
     let mut dockerfile = vec![
-        "FROM node:lts-alpine as development\n",
-        "RUN npm install -g nodemon ts-node\n",
+        "FROM node:20.11-alpine as development\n",
+        &nodemon,
         "WORKDIR /app",
         "COPY package.json /app/package.json",
-        "RUN npm install\n",
+        &install,
         "COPY tsconfig.json /app/tsconfig.json",
         "COPY src /app/src",
         "COPY nodemon.json /app/nodemon.json\n",
@@ -18,14 +32,47 @@ pub fn dockerize(name: &str, config: Option<&str>) -> (String, String, String, S
         "RUN rm /app/tsconfig.json",
         "RUN rm -r /app/src",
         "RUN rm -r /app/node_modules",
-        "RUN npm install --omit=dev\n",
+        &install_prod,
         "# More production build configurations here",
     ];
 
-    let cmd: String;
-    if let Some(conf) = config {
-        cmd = format!("COPY config.{} /app/config.{}", conf, conf);
-        dockerfile.insert(5, &cmd);
+    let test_stage: String;
+    if options.bake {
+        test_stage = format!("FROM development as test\n\nRUN {TEST_COMMAND}\n");
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("FROM development as production"))
+            .unwrap();
+        dockerfile.insert(position, &test_stage);
+    }
+
+    if options.host_user.is_some() {
+        let position = dockerfile
+            .iter()
+            .position(|line| line.starts_with("# More development"))
+            .unwrap();
+        dockerfile.insert(position, utils::DEV_USER);
+    }
+
+    let labels = metadata(name).labels();
+    dockerfile.insert(dockerfile.len() - 1, &labels);
+
+    let health = options
+        .health
+        .as_ref()
+        .map(super::javascript::health_command);
+    let healthcheck: String;
+    if let Some(command) = &health {
+        healthcheck = utils::healthcheck_instruction(command);
+        dockerfile.insert(dockerfile.len() - 1, &healthcheck);
+    }
+
+    if options.hardened {
+        dockerfile.insert(dockerfile.len() - 1, utils::NON_ROOT_USER);
+    }
+
+    if options.buildkit || options.private_registry {
+        dockerfile.insert(0, utils::DOCKERFILE_SYNTAX);
     }
 
     let watch = match config {
@@ -37,79 +84,248 @@ pub fn dockerize(name: &str, config: Option<&str>) -> (String, String, String, S
         None => vec!["src".to_string()],
     };
 
+    let exec = if options.debug {
+        format!("node --inspect=0.0.0.0:{DEBUG_PORT} /usr/local/lib/node_modules/ts-node/dist/bin.js ./src/index.ts")
+    } else {
+        String::from("ts-node ./src/index.ts")
+    };
+
     let nodemon_config = json!({
         "watch": watch,
         "ext": ".ts",
         "ignore": [],
-        "exec": "ts-node ./src/index.ts",
+        "exec": exec,
     });
 
+    let secrets = utils::registry_secrets(
+        options.private_registry,
+        REGISTRY_SECRET.0,
+        REGISTRY_SECRET.1,
+    );
+    let build_args = std::collections::BTreeMap::new();
+    let (app_secrets, app_secret_files) = utils::app_secrets(options.env_secrets);
+    let mut compose_secrets = secrets.clone();
+    compose_secrets.extend(app_secret_files);
+    let (configs, config_files) = utils::app_config(config, "development");
+
     let services = std::collections::HashMap::from([(
         "app".to_string(),
         utils::Service {
-            container_name: name.to_string(),
             build: utils::Build {
                 context: ".",
                 target: "development",
+                args: build_args.clone(),
+                secrets: secrets.keys().cloned().collect(),
             },
             command: String::from("nodemon"),
-            volumes: if config.is_some() {
-                vec![
-                    "./src:/app/src".into(),
-                    format!(
-                        "./config.{}:/app/config.{}",
-                        config.unwrap(),
-                        config.unwrap()
-                    ),
-                ]
+            ports: vec![],
+            configs,
+            secrets: app_secrets.clone(),
+            restart: "unless-stopped",
+            env_file: if options.env {
+                vec![utils::EnvFile::optional(".env")]
             } else {
-                vec!["./src:/app/src".into()]
+                vec![]
             },
-            restart: "unless-stopped",
+            deploy: None,
+            healthcheck: health.as_deref().map(utils::Healthcheck::new),
+            security: utils::Security::default(),
         },
     )]);
 
-    let compose_dev = utils::Compose {
-        version: "3.9",
+    let compose_dev = utils::Compose::new(
         services,
-    };
-
-    let services_prod = std::collections::HashMap::from([(
-        "app".to_string(),
-        utils::Service {
-            container_name: name.to_string(),
-            build: utils::Build {
-                context: ".",
-                target: "production",
-            },
-            command: String::from("npm start"),
-            volumes: vec![],
-            restart: "no",
-        },
-    )]);
+        compose_secrets.clone(),
+        config_files,
+        std::collections::BTreeMap::new(),
+    );
 
-    let compose_prod = utils::Compose {
-        version: "3.9",
-        services: services_prod,
-    };
+    let overlays = options
+        .environments
+        .iter()
+        .map(|environment| {
+            let (restart, deploy) = utils::environment(environment);
+            let (configs, config_files) = utils::app_config(config, environment);
+            let services = std::collections::HashMap::from([(
+                "app".to_string(),
+                utils::Service {
+                    build: utils::Build {
+                        context: ".",
+                        target: "production",
+                        args: build_args.clone(),
+                        secrets: secrets.keys().cloned().collect(),
+                    },
+                    command: String::from("npm start"),
+                    ports: vec![],
+                    configs,
+                    secrets: app_secrets.clone(),
+                    restart,
+                    env_file: vec![utils::EnvFile::optional(&format!(".env.{}", environment))],
+                    deploy: Some(deploy),
+                    healthcheck: health.as_deref().map(utils::Healthcheck::new),
+                    security: utils::Security::new(options.hardened),
+                },
+            )]);
+            let compose = utils::Compose::new(
+                services,
+                compose_secrets.clone(),
+                config_files,
+                std::collections::BTreeMap::new(),
+            );
+            (environment.clone(), to_yaml_string(&compose).unwrap())
+        })
+        .collect();
 
     (
         serde_json::to_string_pretty(&nodemon_config).unwrap(),
         dockerfile.join("\n"),
         to_yaml_string(&compose_dev).unwrap(),
-        to_yaml_string(&compose_prod).unwrap(),
+        overlays,
+    )
+}
+pub fn metadata(name: &str) -> utils::Metadata {
+    super::javascript::metadata(name)
+}
+
+pub fn devcontainer(name: &str, options: &utils::Options) -> String {
+    let mut ports: Vec<u16> = options.health.iter().filter_map(|h| h.port()).collect();
+    if options.debug {
+        ports.push(DEBUG_PORT);
+    }
+
+    utils::devcontainer(
+        name,
+        options.debug,
+        &["dbaeumer.vscode-eslint", "esbenp.prettier-vscode"],
+        &ports,
+        "npm install",
     )
 }
-pub fn scaffold(name: &str) -> (String, String, String) {
-    let index = "console.log('Hello from Typescript!');".to_string();
-    let pkg = json!({
+pub fn dev_compose(options: &utils::Options) -> String {
+    utils::dev_compose(options.host_user, &[("node_modules", "/app/node_modules")])
+}
+pub fn debug_compose() -> String {
+    utils::debug_compose(DEBUG_PORT, false, &[])
+}
+pub fn launch() -> String {
+    let launch = json!({
+        "version": "0.2.0",
+        "configurations": [{
+            "name": "Attach to Docker",
+            "type": "node",
+            "request": "attach",
+            "address": "localhost",
+            "port": DEBUG_PORT,
+            "localRoot": "${workspaceFolder}",
+            "remoteRoot": "/app",
+            "restart": true,
+        }],
+    });
+
+    serde_json::to_string_pretty(&launch).unwrap()
+}
+pub fn scaffold(
+    name: &str,
+    health: Option<&utils::Health>,
+    config: Option<&str>,
+) -> (String, String, String) {
+    let mut dependencies = serde_json::Map::new();
+    let (loader, load) = match config {
+        Some(format) => {
+            let (import, parse, package) = match format {
+                "json" => (None, "JSON.parse", None),
+                "yaml" => (
+                    Some("import * as YAML from \"yaml\";"),
+                    "YAML.parse",
+                    Some(("yaml", "^2.4.1")),
+                ),
+                "ini" => (
+                    Some("import * as ini from \"ini\";"),
+                    "ini.parse",
+                    Some(("ini", "^4.1.2")),
+                ),
+                _ => (
+                    Some("import * as TOML from \"smol-toml\";"),
+                    "TOML.parse",
+                    Some(("smol-toml", "^1.1.4")),
+                ),
+            };
+            if let Some((package, version)) = package {
+                dependencies.insert(package.into(), version.into());
+            }
+            let imports = ["import * as fs from \"fs\";"]
+                .into_iter()
+                .chain(import)
+                .collect::<Vec<&str>>()
+                .join("\n");
+            (
+                format!(
+                    r#"{imports}
+
+interface Config {{
+  port: number;
+  logLevel: string;
+  databaseUrl: string;
+}}
+
+function loadConfig(): Config {{
+  const path = process.env.CONFIG_PATH || "config.{format}";
+  const {{ app }} = {parse}(fs.readFileSync(path, "utf8")) as {{ app: Record<string, unknown> }};
+  return {{
+    port: Number(app.port),
+    logLevel: String(app.log_level),
+    databaseUrl: String(app.database_url),
+  }};
+}}
+"#
+                ),
+                r#"const config = loadConfig();
+console.log(
+  `Loaded config: port=${config.port} log_level=${config.logLevel} database_url=${config.databaseUrl}`,
+);
+"#,
+            )
+        }
+        None => (String::new(), ""),
+    };
+
+    let index = match health.and_then(|h| h.port()) {
+        Some(port) => format!(
+            r#"import * as http from "http";
+{loader}
+{load}const port = Number(process.env.PORT || {default});
+
+http
+  .createServer((req, res) => {{
+    if (req.url === "{path}") {{
+      res.writeHead(200).end("ok");
+      return;
+    }}
+    res.end("Hello from Typescript!");
+  }})
+  .listen(port, () => console.log(`Listening on port ${{port}}`));
+"#,
+            default = if config.is_some() {
+                "config.port".to_string()
+            } else {
+                port.to_string()
+            },
+            path = health.and_then(|h| h.path()).unwrap_or("/health"),
+        ),
+        None if config.is_some() => {
+            format!("{loader}\n{load}console.log('Hello from Typescript!');\n")
+        }
+        None => "console.log('Hello from Typescript!');".to_string(),
+    };
+    let mut pkg = json!({
       "name": name,
       "version": "1.0.0",
       "author": "",
       "license": "",
       "main": "src/index.ts",
-      "dependencies": {},
+      "dependencies": dependencies,
       "devDependencies": {
+        "@types/node": "^20.11.30",
         "typescript": "^5.4.2",
         "rimraf": "^5.0.5",
         "ts-node": "^10.9.2",
@@ -120,6 +336,10 @@ pub fn scaffold(name: &str) -> (String, String, String) {
       },
     });
 
+    if config == Some("ini") {
+        pkg["devDependencies"]["@types/ini"] = json!("^4.1.0");
+    }
+
     let tsconfig = json!({
       "compilerOptions": {
         "target": "es6",
@@ -0,0 +1,326 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub line: usize,
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Instruction with its continuation lines joined, starting at `line` (1-based).
+struct Instruction {
+    line: usize,
+    keyword: String,
+    args: String,
+}
+
+fn parse(contents: &str) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    let mut pending: Option<Instruction> = None;
+    for (index, raw) in contents.lines().enumerate() {
+        let line = raw.trim();
+        if line.starts_with('#') || (pending.is_none() && line.is_empty()) {
+            continue;
+        }
+        let (text, continued) = match line.strip_suffix('\\') {
+            Some(text) => (text.trim_end(), true),
+            None => (line, false),
+        };
+        match pending.as_mut() {
+            Some(instruction) => {
+                instruction.args.push(' ');
+                instruction.args.push_str(text);
+            }
+            None => {
+                let (keyword, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+                pending = Some(Instruction {
+                    line: index + 1,
+                    keyword: keyword.to_uppercase(),
+                    args: args.trim().to_string(),
+                });
+            }
+        }
+        if !continued {
+            instructions.extend(pending.take());
+        }
+    }
+    instructions.extend(pending);
+    instructions
+}
+
+/// Arguments of an instruction without its leading `--flag` options.
+fn operands(args: &str) -> Vec<&str> {
+    args.split_whitespace()
+        .skip_while(|arg| arg.starts_with("--"))
+        .collect()
+}
+
+/// A tag is pinned when it names at least a minor version (`3.12-alpine`) or has a digest.
+fn floating_tag(image: &str) -> Option<String> {
+    if image.contains('@') || image.contains('$') || image == "scratch" {
+        return None;
+    }
+    let name = image.rsplit('/').next().unwrap_or(image);
+    let tag = match name.split_once(':') {
+        Some((_, tag)) => tag,
+        None => return Some(format!("{} has no tag and resolves to latest", image)),
+    };
+    if tag == "latest" {
+        return Some(format!("{} uses the latest tag", image));
+    }
+    let version = tag.split('-').next().unwrap_or(tag);
+    let parts: Vec<&str> = version.trim_start_matches('v').split('.').collect();
+    if parts.len() < 2 || parts.iter().any(|part| part.parse::<u32>().is_err()) {
+        return Some(format!(
+            "{} uses a floating tag, pin at least a minor version",
+            image
+        ));
+    }
+    None
+}
+
+pub fn is_secret(name: &str) -> bool {
+    let name = name.to_uppercase();
+    !name.ends_with("_FILE")
+        && [
+            "SECRET",
+            "PASSWORD",
+            "PASSWD",
+            "TOKEN",
+            "API_KEY",
+            "PRIVATE_KEY",
+        ]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+/// Variable names set by an `ENV` instruction, in both `KEY=value` and legacy `KEY value` forms.
+fn env_names(args: &str) -> Vec<&str> {
+    if args
+        .split_whitespace()
+        .next()
+        .is_some_and(|arg| arg.contains('='))
+    {
+        args.split_whitespace()
+            .filter_map(|pair| pair.split_once('=').map(|(name, _)| name))
+            .collect()
+    } else {
+        args.split_whitespace().take(1).collect()
+    }
+}
+
+const ARCHIVES: [&str; 5] = [".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tar.xz"];
+
+/// Lints a Dockerfile with hadolint-style rules, returning findings ordered by line.
+pub fn lint(contents: &str) -> Vec<Finding> {
+    let instructions = parse(contents);
+    let mut findings = vec![];
+    let mut finding = |line, rule, severity, message: String| {
+        findings.push(Finding {
+            line,
+            rule,
+            severity,
+            message,
+        })
+    };
+
+    let mut stages: Vec<String> = vec![];
+    let mut user: Option<(usize, String)> = None;
+    let mut previous: Option<&str> = None;
+    for instruction in &instructions {
+        let args = instruction.args.as_str();
+        match instruction.keyword.as_str() {
+            "FROM" => {
+                let operands = operands(args);
+                if let Some(image) = operands.first() {
+                    if !stages.iter().any(|stage| stage.eq_ignore_ascii_case(image)) {
+                        if let Some(message) = floating_tag(image) {
+                            finding(instruction.line, "floating-tag", Severity::Warning, message);
+                        }
+                    }
+                }
+                if let [_, keyword, stage] = operands.as_slice() {
+                    if keyword.eq_ignore_ascii_case("as") {
+                        stages.push(stage.to_string());
+                    }
+                }
+                // Each stage starts from its base image's user.
+                user = None;
+            }
+            "RUN" => {
+                let apk_add = args.split(['&', ';', '|']).any(|command| {
+                    let words: Vec<&str> = command.split_whitespace().collect();
+                    words.windows(2).any(|pair| pair == ["apk", "add"])
+                        && !words.contains(&"--no-cache")
+                });
+                if apk_add {
+                    finding(
+                        instruction.line,
+                        "apk-no-cache",
+                        Severity::Warning,
+                        "apk add without --no-cache keeps the package index in the image".into(),
+                    );
+                }
+                if previous == Some("RUN") {
+                    finding(
+                        instruction.line,
+                        "consecutive-run",
+                        Severity::Info,
+                        "consecutive RUN instructions could be merged into one layer".into(),
+                    );
+                }
+            }
+            "ADD" => {
+                let operands = operands(args);
+                let local = operands
+                    .split_last()
+                    .map(|(_, sources)| sources)
+                    .unwrap_or_default()
+                    .iter()
+                    .any(|source| {
+                        !source.contains("://")
+                            && !ARCHIVES.iter().any(|archive| source.ends_with(archive))
+                    });
+                if local {
+                    finding(
+                        instruction.line,
+                        "add-local",
+                        Severity::Warning,
+                        "use COPY instead of ADD for local files and directories".into(),
+                    );
+                }
+            }
+            "ENV" => {
+                for name in env_names(args).into_iter().filter(|name| is_secret(name)) {
+                    finding(
+                        instruction.line,
+                        "env-secret",
+                        Severity::Error,
+                        format!(
+                            "{} looks like a secret and is baked into the image, use a secret mount",
+                            name
+                        ),
+                    );
+                }
+            }
+            "USER" => user = Some((instruction.line, args.to_string())),
+            _ => {}
+        }
+        previous = Some(instruction.keyword.as_str());
+    }
+
+    match user {
+        Some((line, user)) if user == "root" || user.starts_with("root:") || user == "0" => {
+            finding(
+                line,
+                "missing-user",
+                Severity::Warning,
+                "the final stage switches back to root".into(),
+            )
+        }
+        None if !instructions.is_empty() => finding(
+            instructions
+                .last()
+                .map_or(1, |instruction| instruction.line),
+            "missing-user",
+            Severity::Info,
+            "the final stage runs as root, add a USER (see --harden)".into(),
+        ),
+        _ => {}
+    }
+
+    findings.sort_by_key(|finding| finding.line);
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{languages, utils};
+
+    fn dockerfiles() -> Vec<String> {
+        let mut dockerfiles = vec![];
+        for (buildkit, hardened, debug, bake) in [
+            (false, false, false, false),
+            (true, true, true, true),
+            (true, false, false, true),
+        ] {
+            let options = utils::Options {
+                config: Some("json"),
+                buildkit,
+                private_registry: buildkit,
+                hardened,
+                host_user: Some((1000, 1000)),
+                health: Some(utils::Health::parse("/health")),
+                debug,
+                bake,
+                environments: vec!["production".into()],
+                env: true,
+                env_secrets: true,
+            };
+            dockerfiles.extend([
+                languages::rust::dockerize("app", &options).0,
+                languages::golang::dockerize("app", &options).0,
+                languages::python::dockerize("app", &options).0,
+                languages::javascript::dockerize("app", &options).0,
+                languages::typescript::dockerize("app", &options).0,
+            ]);
+        }
+        dockerfiles
+    }
+
+    #[test]
+    fn generated_dockerfiles_lint_clean() {
+        for dockerfile in dockerfiles() {
+            let problems: Vec<_> = lint(&dockerfile)
+                .into_iter()
+                .filter(|finding| finding.severity < Severity::Info)
+                .collect();
+            assert!(problems.is_empty(), "{:?}\n{}", problems, dockerfile);
+        }
+    }
+
+    #[test]
+    fn reports_common_problems() {
+        let dockerfile = "FROM python:3-alpine AS base\n\
+                          RUN apk add --update curl\n\
+                          RUN pip install \\\n    requests\n\
+                          ADD src /app/src\n\
+                          ENV API_TOKEN=abc DEBUG=1\n\
+                          FROM base\n\
+                          USER root\n";
+        let rules: Vec<(usize, &str)> = lint(dockerfile)
+            .iter()
+            .map(|finding| (finding.line, finding.rule))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                (1, "floating-tag"),
+                (2, "apk-no-cache"),
+                (3, "consecutive-run"),
+                (5, "add-local"),
+                (6, "env-secret"),
+                (8, "missing-user"),
+            ]
+        );
+    }
+}
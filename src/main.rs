@@ -1,16 +1,34 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use inquire::{Confirm, Select, Text};
 use spinners::{Spinner, Spinners};
 use std::{env, thread::sleep, time::Duration};
+pub mod compose;
+pub mod config;
+pub mod k8s;
 pub mod languages;
+pub mod lint;
+pub mod manifest;
+pub mod merge;
+pub mod settings;
+pub mod tasks;
 pub mod utils;
 
 #[derive(Parser, Debug)]
 #[command(
     version,
-    about = "🐳 Dockerfile generator cli tool for CI/CD operations."
+    about = "🐳 Dockerfile generator cli tool for CI/CD operations.",
+    args_conflicts_with_subcommands = true
 )]
-struct Args {
+struct Cli {
+    #[command(flatten)]
+    init: InitArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Args, Debug)]
+struct InitArgs {
     #[arg(short, long = "name", value_name = "DIRECTORY", value_hint = clap::ValueHint::DirPath, help = "Target project name")]
     name: Option<String>,
 
@@ -46,12 +64,533 @@ struct Args {
     )]
     makefile: Option<bool>,
 
+    #[arg(
+        long = "runner",
+        value_name = "RUNNER",
+        value_parser = ["make", "just", "task"],
+        ignore_case = true,
+        help = "Task runner file to forge"
+    )]
+    runner: Option<String>,
+
     #[arg(short, long = "env", value_name = "ENABLE", help = "Forge .env file")]
     env: Option<bool>,
+
+    #[arg(
+        long = "env-secrets",
+        value_name = "ENABLE",
+        help = "Deliver sensitive settings as compose secrets instead of .env values (requires --env)"
+    )]
+    env_secrets: Option<bool>,
+
+    #[arg(
+        long = "buildkit",
+        value_name = "ENABLE",
+        help = "Use BuildKit cache mounts in Dockerfiles [default: true]"
+    )]
+    buildkit: Option<bool>,
+
+    #[arg(
+        long = "private-registry",
+        value_name = "ENABLE",
+        help = "Mount package registry credentials as build secrets"
+    )]
+    private_registry: Option<bool>,
+
+    #[arg(
+        long = "harden",
+        value_name = "ENABLE",
+        help = "Run as non-root user with hardened production service"
+    )]
+    harden: Option<bool>,
+
+    #[arg(
+        long = "map-user",
+        value_name = "ENABLE",
+        help = "Run the development container as the invoking host user [default: true]"
+    )]
+    map_user: Option<bool>,
+
+    #[arg(
+        long = "health",
+        value_name = "CHECK",
+        help = "Health check as HTTP path ([PORT]/PATH), TCP port (PORT) or shell command"
+    )]
+    health: Option<String>,
+
+    #[arg(
+        long = "debug",
+        value_name = "ENABLE",
+        help = "Wire a debugger into the development container"
+    )]
+    debug: Option<bool>,
+
+    #[arg(
+        long = "devcontainer",
+        value_name = "ENABLE",
+        help = "Forge .devcontainer/devcontainer.json"
+    )]
+    devcontainer: Option<bool>,
+
+    #[arg(
+        long = "bake",
+        value_name = "ENABLE",
+        help = "Forge docker-bake.hcl for multi-platform builds"
+    )]
+    bake: Option<bool>,
+
+    #[arg(
+        long = "platform",
+        value_name = "PLATFORMS",
+        help = "Comma separated bake platforms [default: linux/amd64,linux/arm64]"
+    )]
+    platform: Option<String>,
+
+    #[arg(
+        long = "environments",
+        value_name = "ENVIRONMENTS",
+        help = "Comma separated compose overlays to forge [default: production]"
+    )]
+    environments: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Forge a new project (default when no subcommand is given)
+    Init(InitArgs),
+    /// Extend an already forged project
+    Add {
+        #[command(subcommand)]
+        target: AddCommand,
+    },
+    /// Re-render the generated files from the saved settings
+    Generate,
+    /// Re-render the generated files with this version's templates
+    Upgrade,
+    /// Report generated files that were modified or removed since DockerForge wrote them
+    Status,
+    /// Remove the generated files that are still untouched
+    Clean,
+    /// Lint a Dockerfile for common problems
+    Lint {
+        #[arg(value_name = "DOCKERFILE", default_value = "Dockerfile")]
+        file: String,
+    },
+    /// Convert an existing stack to another platform
+    Convert {
+        #[command(subcommand)]
+        target: ConvertCommand,
+    },
+    /// Work with compose files
+    Compose {
+        #[command(subcommand)]
+        command: ComposeCommand,
+    },
+    /// Manage the project config files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AddCommand {
+    /// Add a backing service [known kinds: postgres, mysql, redis, mongo, rabbitmq]
+    Service {
+        #[arg(value_name = "KIND")]
+        kind: String,
+        #[arg(long = "name", value_name = "NAME", help = "Compose service name")]
+        name: Option<String>,
+        #[arg(
+            long = "image",
+            value_name = "IMAGE",
+            help = "Image, required for unknown kinds"
+        )]
+        image: Option<String>,
+        #[arg(
+            long = "port",
+            value_name = "PORT",
+            help = "Port published on the host"
+        )]
+        port: Option<u16>,
+    },
+    /// Add a sidecar container sharing the app's network namespace
+    Sidecar {
+        #[arg(value_name = "NAME")]
+        name: String,
+        #[arg(long = "image", value_name = "IMAGE", help = "Sidecar image")]
+        image: String,
+    },
+    /// Add a CI pipeline building and pushing the production image
+    Ci {
+        #[arg(
+            value_name = "PROVIDER",
+            help = "CI provider [possible values: github, gitlab]"
+        )]
+        provider: String,
+    },
+    /// Add Kubernetes manifests for the production image
+    K8s,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConvertCommand {
+    /// Translate a compose file into Kubernetes manifests
+    K8s {
+        #[arg(value_name = "COMPOSE_FILE", default_value = "docker-compose.yaml")]
+        file: String,
+        #[arg(
+            long = "out",
+            value_name = "DIR",
+            default_value = k8s::DIR,
+            help = "Directory the manifests are written to"
+        )]
+        out: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ComposeCommand {
+    /// Validate compose files against the compose-spec schema
+    Validate {
+        #[arg(
+            value_name = "FILES",
+            help = "Compose files, all docker-compose*.yaml files by default"
+        )]
+        files: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Convert the config files to another format and update generated files referencing them
+    Convert {
+        #[arg(
+            long = "to",
+            value_name = "FORMAT",
+            help = "Target config format [possible values: json, yaml, ini, toml]"
+        )]
+        to: String,
+    },
+    /// Validate the config files against config.schema.json
+    Validate,
+}
+
+fn load_settings() -> Option<settings::Settings> {
+    match settings::Settings::load() {
+        Ok(Some(settings)) => Some(settings),
+        Ok(None) => {
+            println!(
+                "❌ No {} found, run `dockerforge init` first !",
+                settings::SETTINGS_FILE
+            );
+            None
+        }
+        Err(e) => {
+            println!("❌ {}", e);
+            None
+        }
+    }
+}
+
+fn run_command(command: Command) {
+    match command {
+        Command::Init(args) => init(args),
+        Command::Add { target } => {
+            let Some(mut settings) = load_settings() else {
+                return;
+            };
+            match target {
+                AddCommand::Service {
+                    kind,
+                    name,
+                    image,
+                    port,
+                } => match settings::ExtraService::new(&kind.to_lowercase(), name, image, port) {
+                    Some(service) => {
+                        let name = service.name.clone();
+                        if let Err(e) = settings.check_service_name(&name) {
+                            println!("❌ {} !", e);
+                            std::process::exit(1);
+                        }
+                        settings.services.push(service);
+                        forge(&settings, false);
+                        settings.save();
+                        println!("🧩 Service {} added!", name);
+                    }
+                    None => println!("❌ Unknown service kind {}, pass --image !", kind),
+                },
+                AddCommand::Sidecar { name, image } => {
+                    if let Err(e) = settings.check_service_name(&name) {
+                        println!("❌ {} !", e);
+                        std::process::exit(1);
+                    }
+                    let service = settings::ExtraService::sidecar(&name, &image);
+                    settings.services.push(service);
+                    forge(&settings, false);
+                    settings.save();
+                    println!("🧩 Sidecar {} added!", name);
+                }
+                AddCommand::Ci { provider } => {
+                    let provider = provider.to_lowercase();
+                    let before = manifest::snapshot(
+                        &utils::ci_file(&provider)
+                            .map(String::from)
+                            .into_iter()
+                            .collect::<Vec<_>>(),
+                    );
+                    match utils::write_ci(&provider, settings.bake, settings.registry_secret()) {
+                        Some(file) => {
+                            if !settings.ci.contains(&provider) {
+                                settings.ci.push(provider);
+                            }
+                            settings.save();
+                            let mut manifest = manifest::Manifest::load();
+                            report_conflicts(&manifest.reconcile(before));
+                            manifest.save();
+                            println!("🔁 {} created!", file);
+                        }
+                        None => println!("❌ Invalid CI provider: {} !", provider),
+                    }
+                }
+                AddCommand::K8s => {
+                    let before = manifest::snapshot(
+                        &k8s::files(settings.config != "none")
+                            .into_iter()
+                            .map(String::from)
+                            .collect::<Vec<_>>(),
+                    );
+                    k8s::write(&settings.name, &settings.options());
+                    settings.k8s = true;
+                    settings.save();
+                    let mut manifest = manifest::Manifest::load();
+                    report_conflicts(&manifest.reconcile(before));
+                    manifest.save();
+                    println!("☸️  Kubernetes manifests created!");
+                }
+            }
+        }
+        Command::Generate => {
+            let Some(settings) = load_settings() else {
+                return;
+            };
+            if settings.version != env!("CARGO_PKG_VERSION") {
+                println!(
+                    "❌ Project was forged with DockerForge {}, run `dockerforge upgrade` to apply the {} templates !",
+                    settings.version,
+                    env!("CARGO_PKG_VERSION")
+                );
+                return;
+            }
+            forge(&settings, false);
+            let mut progress = Spinner::new(Spinners::Aesthetic, String::new());
+            progress.stop_and_persist("🚀", "Generated files re-rendered!".into());
+        }
+        Command::Upgrade => {
+            let Some(mut settings) = load_settings() else {
+                return;
+            };
+            let previous = settings.version.clone();
+            settings.version = env!("CARGO_PKG_VERSION").to_string();
+            forge(&settings, false);
+            settings.save();
+            let mut progress = Spinner::new(Spinners::Aesthetic, String::new());
+            progress.stop_and_persist(
+                "🚀",
+                format!(
+                    "Upgraded templates from {} to {}!",
+                    previous, settings.version
+                ),
+            );
+        }
+        Command::Status => {
+            let manifest = manifest::Manifest::load();
+            if manifest.entries.is_empty() {
+                println!("❌ No {} found !", manifest::MANIFEST_FILE);
+                return;
+            }
+            for (path, status) in manifest.status() {
+                match status {
+                    manifest::Status::Untouched => println!("   untouched  {}", path),
+                    manifest::Status::Modified => println!("✏️  modified   {}", path),
+                    manifest::Status::Missing => println!("❓ missing    {}", path),
+                    manifest::Status::Imported => println!("📥 imported   {}", path),
+                }
+            }
+        }
+        Command::Clean => {
+            let mut manifest = manifest::Manifest::load();
+            if manifest.entries.is_empty() {
+                println!(
+                    "❌ No {} found, nothing to clean !",
+                    manifest::MANIFEST_FILE
+                );
+                return;
+            }
+            let removed = clean(&mut manifest);
+            manifest.save();
+            let mut progress = Spinner::new(Spinners::Aesthetic, String::new());
+            progress.stop_and_persist("🧹", format!("{} generated files removed!", removed));
+        }
+        Command::Lint { file } => {
+            let Some(contents) = utils::read_file(&file) else {
+                println!("❌ Could not read {} !", file);
+                std::process::exit(1);
+            };
+            let findings = lint::lint(&contents);
+            let mut progress = Spinner::new(Spinners::Aesthetic, String::new());
+            if findings.is_empty() {
+                progress.stop_and_persist("✅", format!("{} looks good!", file));
+                return;
+            }
+            progress.stop_and_persist("🔎", format!("{} finding(s) in {}:", findings.len(), file));
+            for finding in &findings {
+                println!(
+                    "   {}:{}: {} [{}] {}",
+                    file, finding.line, finding.severity, finding.rule, finding.message
+                );
+            }
+            if findings
+                .iter()
+                .any(|finding| finding.severity < lint::Severity::Info)
+            {
+                std::process::exit(1);
+            }
+        }
+        Command::Convert {
+            target: ConvertCommand::K8s { file, out },
+        } => {
+            let Some(contents) = utils::read_file(&file) else {
+                println!("❌ Could not read {} !", file);
+                std::process::exit(1);
+            };
+            let mut progress = Spinner::new(
+                Spinners::Aesthetic,
+                format!(" Converting {} to Kubernetes manifests...", file),
+            );
+            match k8s::convert(&contents) {
+                Ok(k8s::Conversion {
+                    manifests,
+                    warnings,
+                }) => {
+                    std::fs::create_dir_all(&out).unwrap();
+                    for (name, manifest) in &manifests {
+                        utils::create_file(&format!("{}/{}", out, name), manifest);
+                    }
+                    progress.stop_and_persist(
+                        "☸️ ",
+                        format!(
+                            "{} Kubernetes manifests written to {}/",
+                            manifests.len(),
+                            out
+                        ),
+                    );
+                    for warning in warnings {
+                        println!("   ⚠️  {}", warning);
+                    }
+                }
+                Err(e) => {
+                    progress.stop_and_persist("❌", format!("Conversion failed: {} !", e));
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Compose {
+            command: ComposeCommand::Validate { files },
+        } => {
+            let mut progress =
+                Spinner::new(Spinners::Aesthetic, " Validating compose files...".into());
+            match compose::validate(&files) {
+                Ok(errors) if errors.is_empty() => {
+                    progress.stop_and_persist("✅", "Compose files are valid!".into())
+                }
+                Ok(errors) => {
+                    progress.stop_and_persist(
+                        "❌",
+                        format!("{} compose error(s) found:", errors.len()),
+                    );
+                    for error in errors {
+                        println!("   {}", error);
+                    }
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    progress.stop_and_persist("❌", format!("Compose validation failed: {} !", e));
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Config {
+            command: ConfigCommand::Convert { to },
+        } => {
+            let to = to.to_lowercase();
+            let mut progress = Spinner::new(
+                Spinners::Aesthetic,
+                format!(" Converting config files to {}...", to),
+            );
+            match config::convert(&to) {
+                Ok(conversion) if conversion.renames.is_empty() => {
+                    progress.stop_and_persist("⭕", format!("Config files are already {}", to))
+                }
+                Ok(conversion) => {
+                    let files: Vec<String> = conversion
+                        .renames
+                        .iter()
+                        .map(|(old, new)| format!("{} → {}", old, new))
+                        .collect();
+                    progress.stop_and_persist(
+                        "🛠️",
+                        format!(" Config files converted: {}", files.join(", ")),
+                    );
+                    if !conversion.sources.is_empty() {
+                        println!(
+                            "📒 Config loader rewritten: {}",
+                            conversion.sources.join(", ")
+                        );
+                    }
+                    report_conflicts(&conversion.conflicts);
+                }
+                Err(e) => {
+                    progress.stop_and_persist("❌", format!("Config conversion failed: {} !", e));
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Config {
+            command: ConfigCommand::Validate,
+        } => {
+            let mut progress =
+                Spinner::new(Spinners::Aesthetic, " Validating config files...".into());
+            match config::validate() {
+                Ok(errors) if errors.is_empty() => {
+                    progress.stop_and_persist("✅", "Config files are valid!".into())
+                }
+                Ok(errors) => {
+                    progress
+                        .stop_and_persist("❌", format!("{} config error(s) found:", errors.len()));
+                    for error in errors {
+                        println!("   {}", error);
+                    }
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    progress.stop_and_persist("❌", format!("Config validation failed: {} !", e));
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
 }
 
 fn main() {
-    let args = Args::parse();
+    let cli = Cli::parse();
+    match cli.command {
+        Some(command) => run_command(command),
+        None => init(cli.init),
+    }
+}
+
+fn init(args: InitArgs) {
     let path = env::current_dir().unwrap();
     let (mut name, mut language, mut config, mut scaffold) = (
         String::from("."),
@@ -64,11 +603,9 @@ fn main() {
         None => {
             match Text::new("Target project name?")
                 .with_default(
-                    &path
-                        .file_name()
+                    path.file_name()
                         .and_then(|name| name.to_str())
-                        .unwrap_or(&name)
-                        .to_string(),
+                        .unwrap_or(&name),
                 )
                 .prompt()
             {
@@ -109,59 +646,175 @@ fn main() {
             Err(_) => println!("❌ An error occured while confirming scaffold !"),
         },
     }
+    let settings = settings::Settings {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        name,
+        language,
+        config,
+        scaffold,
+        env: args.env.unwrap_or(false),
+        env_secrets: args.env_secrets.unwrap_or(false),
+        runner: args
+            .makefile
+            .or(args.runner.as_ref().map(|_| true))
+            .filter(|m| *m)
+            .map(|_| args.runner.as_deref().unwrap_or("make").to_lowercase()),
+        buildkit: args.buildkit.unwrap_or(true),
+        private_registry: args.private_registry.unwrap_or(false),
+        hardened: args.harden.unwrap_or(false),
+        map_user: args.map_user.unwrap_or(true),
+        health: args.health,
+        debug: args.debug.unwrap_or(false),
+        devcontainer: args.devcontainer.unwrap_or(false),
+        bake: args.bake.unwrap_or(false),
+        platforms: args
+            .platform
+            .unwrap_or(String::from("linux/amd64,linux/arm64"))
+            .split(',')
+            .map(|platform| platform.trim().to_string())
+            .collect(),
+        environments: args
+            .environments
+            .as_deref()
+            .unwrap_or("production")
+            .split(',')
+            .map(|environment| environment.trim().to_lowercase())
+            .filter(|environment| !environment.is_empty())
+            .collect(),
+        services: vec![],
+        ci: vec![],
+        k8s: false,
+    };
+    if let Err(e) = tasks::check_environments(&settings.environments) {
+        println!("❌ {} !", e);
+        std::process::exit(1);
+    }
     let mut progress = Spinner::new(Spinners::Aesthetic, String::new());
     progress.stop_and_persist("🐋", "Starting Dockerforge...".into());
-    if config != "none" {
-        let mut progress = Spinner::new(Spinners::Aesthetic, " Creating config file...".into());
-        utils::write_config(&config);
-        sleep(Duration::from_millis(250));
-        progress.stop_and_persist("🛠️", " Config file created!".into());
+    if args.env == Some(false) {
+        let mut progress = Spinner::new(Spinners::Aesthetic, String::new());
+        progress.stop_and_persist("⭕", "Skipping .env file creation...".into());
+    }
+    if args.makefile == Some(false) {
+        let mut progress = Spinner::new(Spinners::Aesthetic, String::new());
+        progress.stop_and_persist("⭕", "Skipping Makefile creation...".into());
     }
+    forge(&settings, true);
+    settings.save();
+    let mut progress = Spinner::new(Spinners::Aesthetic, String::new());
+    progress.stop_and_persist("🚀", "DockerForge complete!".into());
 
-    if let Some(e) = args.env {
-        let mut progress = Spinner::new(Spinners::Aesthetic, " Creating .env file...".into());
-        if e {
-            utils::write_env();
+    // if let Some(s) = args.scaffold {
+    //     if s {
+    //         let mut progress = Spinner::new(
+    //             Spinners::Aesthetic,
+    //             format!(" Scaffolding {} Template...", language),
+    //         );
+    //         // utils::write_dockerfiles();
+    //         sleep(Duration::from_millis(250));
+    //         progress.stop_and_persist("📒", format!("Scaffolded {} template!", &language));
+    //     }
+    // }
+    // let mut sp = Spinner::new(Spinners::Aesthetic, "Fetching your name...".into());
+    // sleep(Duration::from_secs(1));
+}
+
+/// Renders every generated file from the settings; `fresh` also writes config, env and scaffold.
+fn forge(settings: &settings::Settings, fresh: bool) {
+    let (name, language, config) = (&settings.name, &settings.language, &settings.config);
+    let scaffold = fresh && settings.scaffold;
+    let options = settings.options();
+    let compose_files = compose_files(settings, &options);
+    // Compose files are reconciled and recorded by `compose::write` itself.
+    let before = manifest::snapshot(
+        &settings
+            .artifacts()
+            .into_iter()
+            .filter(|file| !compose_files.iter().any(|(compose, _)| compose == file))
+            .collect::<Vec<_>>(),
+    );
+    let seeds = manifest::snapshot(&settings.seeds());
+    if let Err(errors) = compose::write(&compose_files) {
+        println!("❌ Refusing to write invalid compose files, nothing was changed:");
+        for error in errors {
+            println!("   {}", error);
+        }
+        std::process::exit(1);
+    }
+    if config != "none" {
+        if fresh {
+            let mut progress = Spinner::new(Spinners::Aesthetic, " Creating config file...".into());
+            let port = options
+                .health
+                .as_ref()
+                .and_then(|h| h.port())
+                .unwrap_or(utils::APP_PORT);
+            utils::write_config(config, "development", port);
+            for environment in &options.environments {
+                utils::write_config(config, environment, port);
+            }
             sleep(Duration::from_millis(250));
-            progress.stop_and_persist("⚙️", " Env file created!".into());
-        } else {
-            progress.stop_and_persist("⭕", "Skipping .env file creation...".into());
+            progress.stop_and_persist("🛠️", " Config file created!".into());
         }
+        config::write_schema();
     }
-    if let Some(m) = args.makefile {
+
+    if fresh && settings.env {
+        let mut progress = Spinner::new(Spinners::Aesthetic, " Creating .env file...".into());
+        utils::write_env(name, language, &options);
+        sleep(Duration::from_millis(250));
+        progress.stop_and_persist("⚙️", " Env files created!".into());
+    }
+    if let Some(runner) = &settings.runner {
         let mut progress = Spinner::new(Spinners::Aesthetic, " Creating Makefile...".into());
-        if m {
-            utils::write_makefile(&name, Some(&language));
-            sleep(Duration::from_millis(250));
-            progress.stop_and_persist("📙", "Makefile created!".into());
-        } else {
-            progress.stop_and_persist("⭕", "Skipping Makefile creation...".into());
+        match utils::write_tasks(name, Some(language), &options, runner) {
+            Some(file) => {
+                sleep(Duration::from_millis(250));
+                progress.stop_and_persist("📙", format!("{} created!", file));
+            }
+            None => {
+                progress.stop_and_persist("❌", format!("Invalid runner selection: {} !", runner))
+            }
         }
     }
+    if options.bake {
+        let mut progress = Spinner::new(Spinners::Aesthetic, " Creating bake file...".into());
+        utils::write_bake(
+            name,
+            &settings
+                .platforms
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+            settings.registry_secret(),
+        );
+        sleep(Duration::from_millis(250));
+        progress.stop_and_persist("🍞", "Bake file created!".into());
+    }
     let mut progress = Spinner::new(Spinners::Aesthetic, " Creating Dockerfiles...".into());
     match language.as_str() {
         "Typescript" => {
-            let (nodemon, dockerfile, compose_dev, compose_prod) = languages::typescript::dockerize(
-                &name,
-                if config != "none" {
-                    Some(&config)
-                } else {
-                    None
-                },
-            );
-            utils::write_dockerfiles(&dockerfile, &compose_dev, &compose_prod, Some(&nodemon));
+            let (nodemon, dockerfile, _, _) = languages::typescript::dockerize(name, &options);
+            utils::write_dockerfiles(&dockerfile, &options.environments, Some(&nodemon));
+            if options.debug {
+                utils::write_launch(&languages::typescript::launch());
+            }
+            if settings.devcontainer {
+                utils::write_devcontainer(&languages::typescript::devcontainer(name, &options));
+            }
             sleep(Duration::from_millis(250));
-            progress.stop_and_persist("🐳", format!("{} dockerfiles created!", &language));
+            progress.stop_and_persist("🐳", format!("{} dockerfiles created!", language));
             if scaffold {
                 let mut progress = Spinner::new(
                     Spinners::Aesthetic,
                     " Scaffolding template project...".into(),
                 );
-                let (index, pkg, tsconfig) = languages::typescript::scaffold(&name);
                 if utils::create_src_dir() {
-                    utils::create_file("src/index.ts", &index);
-                    utils::create_file("package.json", &pkg);
-                    utils::create_file("tsconfig.json", &tsconfig);
+                    for (file, contents) in
+                        languages::scaffold(language, name, options.health.as_ref(), options.config)
+                    {
+                        utils::create_file(file, &contents);
+                    }
                 } else {
                     progress.stop_and_persist("❌", "Error scaffolding template!".into());
                 }
@@ -170,26 +823,27 @@ fn main() {
             }
         }
         "Javascript" => {
-            let (nodemon, dockerfile, compose_dev, compose_prod) = languages::javascript::dockerize(
-                &name,
-                if config != "none" {
-                    Some(&config)
-                } else {
-                    None
-                },
-            );
-            utils::write_dockerfiles(&dockerfile, &compose_dev, &compose_prod, Some(&nodemon));
+            let (nodemon, dockerfile, _, _) = languages::javascript::dockerize(name, &options);
+            utils::write_dockerfiles(&dockerfile, &options.environments, Some(&nodemon));
+            if options.debug {
+                utils::write_launch(&languages::javascript::launch());
+            }
+            if settings.devcontainer {
+                utils::write_devcontainer(&languages::javascript::devcontainer(name, &options));
+            }
             sleep(Duration::from_millis(250));
-            progress.stop_and_persist("🐳", format!("{} dockerfiles created!", &language));
+            progress.stop_and_persist("🐳", format!("{} dockerfiles created!", language));
             if scaffold {
                 let mut progress = Spinner::new(
                     Spinners::Aesthetic,
                     " Scaffolding template project...".into(),
                 );
-                let (index, pkg) = languages::javascript::scaffold(&name);
                 if utils::create_src_dir() {
-                    utils::create_file("src/index.js", &index);
-                    utils::create_file("package.json", &pkg);
+                    for (file, contents) in
+                        languages::scaffold(language, name, options.health.as_ref(), options.config)
+                    {
+                        utils::create_file(file, &contents);
+                    }
                 } else {
                     progress.stop_and_persist("❌", "Error scaffolding template!".into());
                 }
@@ -198,26 +852,27 @@ fn main() {
             }
         }
         "Python" => {
-            let (nodemon, dockerfile, compose_dev, compose_prod) = languages::python::dockerize(
-                &name,
-                if config != "none" {
-                    Some(&config)
-                } else {
-                    None
-                },
-            );
-            utils::write_dockerfiles(&dockerfile, &compose_dev, &compose_prod, Some(&nodemon));
+            let (nodemon, dockerfile, _, _) = languages::python::dockerize(name, &options);
+            utils::write_dockerfiles(&dockerfile, &options.environments, Some(&nodemon));
+            if options.debug {
+                utils::write_launch(&languages::python::launch());
+            }
+            if settings.devcontainer {
+                utils::write_devcontainer(&languages::python::devcontainer(name, &options));
+            }
             sleep(Duration::from_millis(250));
-            progress.stop_and_persist("🐳", format!("{} dockerfiles created!", &language));
+            progress.stop_and_persist("🐳", format!("{} dockerfiles created!", language));
             if scaffold {
                 let mut progress = Spinner::new(
                     Spinners::Aesthetic,
                     " Scaffolding template project...".into(),
                 );
-                let index = languages::python::scaffold();
                 if utils::create_src_dir() {
-                    utils::create_file("src/index.py", &index);
-                    utils::create_file("requirements.txt", &"");
+                    for (file, contents) in
+                        languages::scaffold(language, name, options.health.as_ref(), options.config)
+                    {
+                        utils::create_file(file, &contents);
+                    }
                 } else {
                     progress.stop_and_persist("❌", "Error scaffolding template!".into());
                 }
@@ -226,26 +881,27 @@ fn main() {
             }
         }
         "Rust" => {
-            let (nodemon, dockerfile, compose_dev, compose_prod) = languages::rust::dockerize(
-                &name,
-                if config != "none" {
-                    Some(&config)
-                } else {
-                    None
-                },
-            );
-            utils::write_dockerfiles(&dockerfile, &compose_dev, &compose_prod, Some(&nodemon));
+            let (nodemon, dockerfile, _, _) = languages::rust::dockerize(name, &options);
+            utils::write_dockerfiles(&dockerfile, &options.environments, Some(&nodemon));
+            if options.debug {
+                utils::write_launch(&languages::rust::launch(name));
+            }
+            if settings.devcontainer {
+                utils::write_devcontainer(&languages::rust::devcontainer(name, &options));
+            }
             sleep(Duration::from_millis(250));
-            progress.stop_and_persist("🐳", format!("{} dockerfiles created!", &language));
+            progress.stop_and_persist("🐳", format!("{} dockerfiles created!", language));
             if scaffold {
                 let mut progress = Spinner::new(
                     Spinners::Aesthetic,
                     " Scaffolding template project...".into(),
                 );
-                let (main, cargo) = languages::rust::scaffold(&name);
                 if utils::create_src_dir() {
-                    utils::create_file("src/main.rs", &main);
-                    utils::create_file("Cargo.toml", &cargo);
+                    for (file, contents) in
+                        languages::scaffold(language, name, options.health.as_ref(), options.config)
+                    {
+                        utils::create_file(file, &contents);
+                    }
                 } else {
                     progress.stop_and_persist("❌", "Error scaffolding template!".into());
                 }
@@ -254,26 +910,27 @@ fn main() {
             }
         }
         "Golang" => {
-            let (nodemon, dockerfile, compose_dev, compose_prod) = languages::golang::dockerize(
-                &name,
-                if config != "none" {
-                    Some(&config)
-                } else {
-                    None
-                },
-            );
-            utils::write_dockerfiles(&dockerfile, &compose_dev, &compose_prod, Some(&nodemon));
+            let (nodemon, dockerfile, _, _) = languages::golang::dockerize(name, &options);
+            utils::write_dockerfiles(&dockerfile, &options.environments, Some(&nodemon));
+            if options.debug {
+                utils::write_launch(&languages::golang::launch());
+            }
+            if settings.devcontainer {
+                utils::write_devcontainer(&languages::golang::devcontainer(name, &options));
+            }
             sleep(Duration::from_millis(250));
-            progress.stop_and_persist("🐳", format!("{} dockerfiles created!", &language));
+            progress.stop_and_persist("🐳", format!("{} dockerfiles created!", language));
             if scaffold {
                 let mut progress = Spinner::new(
                     Spinners::Aesthetic,
                     " Scaffolding template project...".into(),
                 );
-                let (main, module) = languages::golang::scaffold(&name);
                 if utils::create_src_dir() {
-                    utils::create_file("src/main.go", &main);
-                    utils::create_file("go.mod", &module);
+                    for (file, contents) in
+                        languages::scaffold(language, name, options.health.as_ref(), options.config)
+                    {
+                        utils::create_file(file, &contents);
+                    }
                 } else {
                     progress.stop_and_persist("❌", "Error scaffolding template!".into());
                 }
@@ -282,23 +939,111 @@ fn main() {
             }
         }
         _ => {
-            progress.stop_and_persist("❌", format!("Invalid language selection: {} !", &language));
+            progress.stop_and_persist("❌", format!("Invalid language selection: {} !", language));
         }
     }
-    let mut progress = Spinner::new(Spinners::Aesthetic, String::new());
-    progress.stop_and_persist("🚀", "DockerForge complete!".into());
+    for provider in &settings.ci {
+        utils::write_ci(provider, settings.bake, settings.registry_secret());
+    }
+    if settings.k8s {
+        k8s::write(name, &options);
+    }
 
-    // if let Some(s) = args.scaffold {
-    //     if s {
-    //         let mut progress = Spinner::new(
-    //             Spinners::Aesthetic,
-    //             format!(" Scaffolding {} Template...", language),
-    //         );
-    //         // utils::write_dockerfiles();
-    //         sleep(Duration::from_millis(250));
-    //         progress.stop_and_persist("📒", format!("Scaffolded {} template!", &language));
-    //     }
-    // }
-    // let mut sp = Spinner::new(Spinners::Aesthetic, "Fetching your name...".into());
-    // sleep(Duration::from_secs(1));
+    let mut manifest = manifest::Manifest::load();
+    report_conflicts(&manifest.reconcile(before));
+    manifest.record_seeds(seeds);
+    manifest.save();
+}
+
+/// Compose files forged for the project: the base stack, the development and debugger overlays
+/// and the environment overlays.
+fn compose_files(settings: &settings::Settings, options: &utils::Options) -> Vec<(String, String)> {
+    let name = &settings.name;
+    let ((_, _, compose_dev, overlays), dev, debug) = match settings.language.as_str() {
+        "Typescript" => (
+            languages::typescript::dockerize(name, options),
+            languages::typescript::dev_compose(options),
+            languages::typescript::debug_compose(),
+        ),
+        "Javascript" => (
+            languages::javascript::dockerize(name, options),
+            languages::javascript::dev_compose(options),
+            languages::javascript::debug_compose(),
+        ),
+        "Python" => (
+            languages::python::dockerize(name, options),
+            languages::python::dev_compose(options),
+            languages::python::debug_compose(),
+        ),
+        "Rust" => (
+            languages::rust::dockerize(name, options),
+            languages::rust::dev_compose(options),
+            languages::rust::debug_compose(name),
+        ),
+        "Golang" => (
+            languages::golang::dockerize(name, options),
+            languages::golang::dev_compose(options),
+            languages::golang::debug_compose(),
+        ),
+        _ => return vec![],
+    };
+    let mut files = vec![(
+        "docker-compose.yaml".to_string(),
+        utils::add_services(&compose_dev, &settings.services),
+    )];
+    files.push((utils::DEV_COMPOSE_FILE.to_string(), dev));
+    files.extend(
+        overlays.into_iter().map(|(environment, overlay)| {
+            (format!("docker-compose.{}.yaml", environment), overlay)
+        }),
+    );
+    if options.debug {
+        files.push((utils::DEBUG_COMPOSE_FILE.to_string(), debug));
+    }
+    files
+}
+
+/// Removes the generated files that are still untouched, returning how many were removed.
+fn clean(manifest: &mut manifest::Manifest) -> usize {
+    let mut removed = 0;
+    for (file, status) in manifest.status() {
+        match status {
+            manifest::Status::Modified => {
+                println!("✏️  Keeping modified {}", file);
+                continue;
+            }
+            manifest::Status::Imported => {
+                println!("📥 Keeping imported {}", file);
+                continue;
+            }
+            manifest::Status::Untouched => {
+                // Keep failed files in the manifest so the next clean retries them.
+                if let Err(e) = std::fs::remove_file(&file) {
+                    println!("⚠️  Could not remove {}: {}", file, e);
+                    continue;
+                }
+                removed += 1;
+                // Drop directories DockerForge created once they are empty.
+                let mut dir = std::path::Path::new(&file).parent();
+                while let Some(path) = dir.filter(|path| !path.as_os_str().is_empty()) {
+                    if std::fs::remove_dir(path).is_err() {
+                        break;
+                    }
+                    dir = path.parent();
+                }
+            }
+            manifest::Status::Missing => {}
+        }
+        manifest.forget(&file);
+    }
+    removed
+}
+
+fn report_conflicts(conflicted: &[String]) {
+    for file in conflicted {
+        println!(
+            "⚔️  {} has conflicting hand edits, resolve the <<<<<<< markers",
+            file
+        );
+    }
 }
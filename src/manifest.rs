@@ -0,0 +1,155 @@
+use crate::{merge, utils};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{create_dir_all, remove_file};
+use std::path::Path;
+
+/// `sha256sum` compatible list of generated files, so `sha256sum -c` works on it too.
+pub const MANIFEST_FILE: &str = ".dockerforge/manifest";
+/// Copies of the generated files, used as the merge base when regenerating.
+pub const BASE_DIR: &str = ".dockerforge/base";
+/// Files that existed before DockerForge first wrote them, one path per line.
+pub const IMPORTED_FILE: &str = ".dockerforge/imported";
+
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    Untouched,
+    Modified,
+    Missing,
+    /// Existing file DockerForge merged its entries into, never removed by `clean`.
+    Imported,
+}
+
+#[derive(Default)]
+pub struct Manifest {
+    /// Recorded content hash by path.
+    pub entries: BTreeMap<String, String>,
+    /// Recorded files that were imported rather than created.
+    pub imported: BTreeSet<String>,
+}
+
+pub fn hash(contents: &str) -> String {
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Contents of `files` before they are regenerated, to be passed to [`Manifest::reconcile`].
+pub fn snapshot(files: &[String]) -> Vec<(String, Option<String>)> {
+    files
+        .iter()
+        .map(|file| (file.clone(), utils::read_file(file)))
+        .collect()
+}
+
+impl Manifest {
+    pub fn load() -> Self {
+        let entries = utils::read_file(MANIFEST_FILE)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (hash, path) = line.split_once("  ")?;
+                Some((path.to_string(), hash.to_string()))
+            })
+            .collect();
+        let imported = utils::read_file(IMPORTED_FILE)
+            .unwrap_or_default()
+            .lines()
+            .map(String::from)
+            .collect();
+        Manifest { entries, imported }
+    }
+
+    pub fn save(&self) {
+        create_dir_all(crate::settings::SETTINGS_DIR).unwrap();
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|(path, hash)| format!("{}  {}\n", hash, path))
+            .collect();
+        utils::create_file(MANIFEST_FILE, &contents);
+        if self.imported.is_empty() {
+            let _ = remove_file(IMPORTED_FILE);
+        } else {
+            let imported: String = self
+                .imported
+                .iter()
+                .map(|path| format!("{}\n", path))
+                .collect();
+            utils::create_file(IMPORTED_FILE, &imported);
+        }
+    }
+
+    /// Records a freshly generated file and keeps a copy of it as the next merge base.
+    pub fn record(&mut self, file: &str, generated: &str) {
+        let base = format!("{}/{}", BASE_DIR, file);
+        if let Some(dir) = Path::new(&base).parent() {
+            create_dir_all(dir).unwrap();
+        }
+        utils::create_file(&base, generated);
+        self.entries.insert(file.to_string(), hash(generated));
+    }
+
+    /// Copy of `file` as DockerForge last generated it.
+    pub fn base(&self, file: &str) -> Option<String> {
+        utils::read_file(&format!("{}/{}", BASE_DIR, file))
+    }
+
+    pub fn forget(&mut self, file: &str) {
+        let _ = remove_file(format!("{}/{}", BASE_DIR, file));
+        self.entries.remove(file);
+        self.imported.remove(file);
+    }
+
+    /// Merges hand edits from `before` (file contents prior to regeneration) into the freshly
+    /// written files and records the new output, returning the files left with conflicts.
+    pub fn reconcile(&mut self, before: Vec<(String, Option<String>)>) -> Vec<String> {
+        let mut conflicted = vec![];
+        for (file, current) in before {
+            let Some(generated) = utils::read_file(&file) else {
+                continue;
+            };
+            let base = self.base(&file);
+            if let (Some(current), Some(base)) = (current, base) {
+                if current != base && current != generated {
+                    let (merged, conflicts) = merge::merge(&base, &current, &generated);
+                    utils::create_file(&file, &merged);
+                    if conflicts {
+                        conflicted.push(file.clone());
+                    }
+                }
+            }
+            self.record(&file, &generated);
+        }
+        conflicted
+    }
+
+    /// Records the seed files from `before` (contents prior to `forge`) that were just created
+    /// or rewritten. The others are left to the user and never recorded.
+    pub fn record_seeds(&mut self, before: Vec<(String, Option<String>)>) {
+        for (file, previous) in before {
+            match utils::read_file(&file) {
+                Some(contents) if previous.as_ref() != Some(&contents) => {
+                    self.record(&file, &contents)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn status(&self) -> Vec<(String, Status)> {
+        self.entries
+            .iter()
+            .map(|(path, recorded)| {
+                let status = match utils::read_file(path) {
+                    None => Status::Missing,
+                    Some(_) if self.imported.contains(path) => Status::Imported,
+                    Some(contents) if hash(&contents) == *recorded => Status::Untouched,
+                    Some(_) => Status::Modified,
+                };
+                (path.clone(), status)
+            })
+            .collect()
+    }
+}
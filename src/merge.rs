@@ -0,0 +1,250 @@
+use serde_yaml::{Mapping, Value as Yaml};
+
+/// Line based three-way merge in the style of `diff3 -m`.
+///
+/// `base` is the previously generated file, `current` the file on disk and `generated` the new
+/// output. Regions changed on only one side are taken from that side; regions changed
+/// differently on both sides are written with conflict markers. Returns the merged text and
+/// whether it contains conflicts.
+pub fn merge(base: &str, current: &str, generated: &str) -> (String, bool) {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let current: Vec<&str> = current.split_inclusive('\n').collect();
+    let generated: Vec<&str> = generated.split_inclusive('\n').collect();
+    let to_current = matches(&base, &current);
+    let to_generated = matches(&base, &generated);
+
+    let mut merged = String::new();
+    let mut conflicts = false;
+    let (mut b, mut c, mut g) = (0, 0, 0);
+    loop {
+        // Next base line kept by both sides, which closes the current unstable chunk.
+        let stable =
+            (b..base.len()).find(|&i| to_current[i].is_some() && to_generated[i].is_some());
+        match stable {
+            Some(i) if i == b && to_current[i] == Some(c) && to_generated[i] == Some(g) => {
+                merged.push_str(base[b]);
+                b += 1;
+                c += 1;
+                g += 1;
+            }
+            Some(i) => {
+                let (next_c, next_g) = (to_current[i].unwrap(), to_generated[i].unwrap());
+                conflicts |= resolve(
+                    &base[b..i],
+                    &current[c..next_c],
+                    &generated[g..next_g],
+                    &mut merged,
+                );
+                (b, c, g) = (i, next_c, next_g);
+            }
+            None => {
+                conflicts |= resolve(&base[b..], &current[c..], &generated[g..], &mut merged);
+                break;
+            }
+        }
+    }
+    (merged, conflicts)
+}
+
+/// Writes an unstable chunk, returning whether it conflicted.
+fn resolve(base: &[&str], current: &[&str], generated: &[&str], merged: &mut String) -> bool {
+    if current == base || current == generated {
+        merged.push_str(&generated.concat());
+        false
+    } else if generated == base {
+        merged.push_str(&current.concat());
+        false
+    } else {
+        let block = |lines: &[&str]| {
+            let mut block = lines.concat();
+            if !block.is_empty() && !block.ends_with('\n') {
+                block.push('\n');
+            }
+            block
+        };
+        merged.push_str("<<<<<<< current\n");
+        merged.push_str(&block(current));
+        merged.push_str("=======\n");
+        merged.push_str(&block(generated));
+        merged.push_str(">>>>>>> generated\n");
+        true
+    }
+}
+
+/// Key by key three-way merge of YAML documents, for files whose line layout is rewritten on
+/// every render.
+///
+/// Mappings are merged per key, keeping `current`'s key order and appending keys new in
+/// `generated`. Any other value changed differently on both sides is a conflict, returned as
+/// its dotted path. `None` stands for an absent key.
+pub fn merge_yaml(
+    base: Option<&Yaml>,
+    current: Option<&Yaml>,
+    generated: Option<&Yaml>,
+) -> Result<Option<Yaml>, String> {
+    merge_value("", base, current, generated)
+}
+
+fn merge_value(
+    path: &str,
+    base: Option<&Yaml>,
+    current: Option<&Yaml>,
+    generated: Option<&Yaml>,
+) -> Result<Option<Yaml>, String> {
+    if current == base {
+        return Ok(generated.cloned());
+    }
+    if generated == base || current == generated {
+        return Ok(current.cloned());
+    }
+    let (Some(Yaml::Mapping(current)), Some(Yaml::Mapping(generated))) = (current, generated)
+    else {
+        return Err(path.to_string());
+    };
+    let empty = Mapping::new();
+    let base = match base {
+        Some(Yaml::Mapping(base)) => base,
+        _ => &empty,
+    };
+    let mut merged = Mapping::new();
+    for key in current
+        .keys()
+        .chain(generated.keys().filter(|key| !current.contains_key(*key)))
+    {
+        let name = key
+            .as_str()
+            .map(String::from)
+            .unwrap_or_else(|| format!("{:?}", key));
+        let child = if path.is_empty() {
+            name
+        } else {
+            format!("{}.{}", path, name)
+        };
+        if let Some(value) =
+            merge_value(&child, base.get(key), current.get(key), generated.get(key))?
+        {
+            merged.insert(key.clone(), value);
+        }
+    }
+    Ok(Some(Yaml::Mapping(merged)))
+}
+
+/// For each line of `a`, the index of the line it is matched with in `b` by a longest common
+/// subsequence.
+fn matches(a: &[&str], b: &[&str]) -> Vec<Option<usize>> {
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut matched = vec![None; a.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            matched[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "FROM alpine:3.20\nWORKDIR /app\nCOPY . .\nCMD [\"app\"]\n";
+
+    #[test]
+    fn keeps_changes_made_only_by_the_user() {
+        let current = BASE.replace("WORKDIR /app", "WORKDIR /srv");
+        assert_eq!(merge(BASE, &current, BASE), (current, false));
+    }
+
+    #[test]
+    fn takes_changes_made_only_by_the_generator() {
+        let generated = BASE.replace("alpine:3.20", "alpine:3.21");
+        assert_eq!(merge(BASE, BASE, &generated), (generated, false));
+    }
+
+    #[test]
+    fn marks_both_sides_changing_the_same_lines() {
+        let current = BASE.replace("COPY . .", "COPY src src");
+        let generated = BASE.replace("COPY . .", "COPY --link . .");
+        assert_eq!(
+            merge(BASE, &current, &generated),
+            (
+                "FROM alpine:3.20\n\
+                 WORKDIR /app\n\
+                 <<<<<<< current\n\
+                 COPY src src\n\
+                 =======\n\
+                 COPY --link . .\n\
+                 >>>>>>> generated\n\
+                 CMD [\"app\"]\n"
+                    .to_string(),
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn merges_insertions_at_the_start_and_end() {
+        let current = format!("# syntax=docker/dockerfile:1\n{}", BASE);
+        let generated = format!("{}USER app\n", BASE);
+        assert_eq!(
+            merge(BASE, &current, &generated),
+            (
+                format!("# syntax=docker/dockerfile:1\n{}USER app\n", BASE),
+                false
+            )
+        );
+    }
+
+    #[test]
+    fn merges_against_an_empty_base() {
+        assert_eq!(merge("", "", BASE), (BASE.to_string(), false));
+        assert_eq!(merge("", BASE, ""), (BASE.to_string(), false));
+        assert_eq!(
+            merge("", "a\n", "b"),
+            (
+                "<<<<<<< current\na\n=======\nb\n>>>>>>> generated\n".to_string(),
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn accepts_identical_changes_on_both_sides() {
+        let edited = BASE.replace("CMD [\"app\"]", "CMD [\"app\", \"--serve\"]");
+        assert_eq!(merge(BASE, &edited, &edited), (edited.clone(), false));
+    }
+
+    #[test]
+    fn merges_yaml_key_by_key() {
+        let yaml = |text: &str| serde_yaml::from_str::<Yaml>(text).unwrap();
+        let base = yaml("app:\n  image: app\n  healthcheck: curl\n");
+        let current = yaml("app:\n  image: app\n  healthcheck: curl\n  environment: {A: b}\n");
+        let generated = yaml("app:\n  image: app:2\n");
+        assert_eq!(
+            merge_yaml(Some(&base), Some(&current), Some(&generated)),
+            Ok(Some(yaml("app:\n  image: app:2\n  environment: {A: b}\n")))
+        );
+
+        let current = yaml("app:\n  image: mine\n  healthcheck: curl\n");
+        assert_eq!(
+            merge_yaml(Some(&base), Some(&current), Some(&generated)),
+            Err("app.image".to_string())
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::create_dir_all;
+
+pub const SETTINGS_DIR: &str = ".dockerforge";
+pub const SETTINGS_FILE: &str = ".dockerforge/settings.toml";
+
+/// Answers given to `init`, saved so `generate` and `upgrade` can re-render without prompting.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Settings {
+    /// DockerForge version whose templates rendered the project.
+    pub version: String,
+    pub name: String,
+    pub language: String,
+    pub config: String,
+    pub scaffold: bool,
+    pub env: bool,
+    pub env_secrets: bool,
+    pub runner: Option<String>,
+    pub buildkit: bool,
+    pub private_registry: bool,
+    pub hardened: bool,
+    pub map_user: bool,
+    pub health: Option<String>,
+    pub debug: bool,
+    pub devcontainer: bool,
+    pub bake: bool,
+    pub platforms: Vec<String>,
+    pub environments: Vec<String>,
+    #[serde(default)]
+    pub services: Vec<ExtraService>,
+    #[serde(default)]
+    pub ci: Vec<String>,
+    #[serde(default)]
+    pub k8s: bool,
+}
+
+/// Container added next to the app with `dockerforge add service|sidecar`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExtraService {
+    pub name: String,
+    pub image: String,
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+    /// Data directory persisted in a named volume.
+    pub data: Option<String>,
+    /// Sidecars share the app's network namespace instead of being a dependency of it.
+    #[serde(default)]
+    pub sidecar: bool,
+}
+
+impl Settings {
+    /// Saved settings, `None` outside a forged project and an error when the file doesn't parse.
+    pub fn load() -> Result<Option<Self>, String> {
+        match utils::read_file(SETTINGS_FILE) {
+            Some(contents) => toml::from_str::<Self>(&contents)
+                .map_err(|e| e.to_string())
+                .and_then(|settings| {
+                    crate::tasks::check_environments(&settings.environments)?;
+                    Ok(Some(settings))
+                })
+                .map_err(|e| format!("{}: {}", SETTINGS_FILE, e)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn save(&self) {
+        create_dir_all(SETTINGS_DIR).unwrap();
+        utils::create_file(SETTINGS_FILE, &toml::to_string(self).unwrap());
+    }
+
+    pub fn options(&self) -> utils::Options<'_> {
+        utils::Options {
+            config: if self.config != "none" {
+                Some(&self.config)
+            } else {
+                None
+            },
+            buildkit: self.buildkit,
+            private_registry: self.private_registry,
+            hardened: self.hardened,
+            host_user: if self.map_user {
+                utils::host_user()
+            } else {
+                None
+            },
+            health: self.health.as_deref().map(utils::Health::parse),
+            debug: self.debug,
+            bake: self.bake,
+            environments: self.environments.clone(),
+            env: self.env,
+            env_secrets: self.env && self.env_secrets,
+        }
+    }
+
+    /// Build secret `(id, source)` with the package registry credentials, if any.
+    pub fn registry_secret(&self) -> Option<(&'static str, &'static str)> {
+        crate::languages::registry_secret(&self.language).filter(|_| self.private_registry)
+    }
+
+    /// Checks the compose service name of a new `add service|sidecar` container, which must not
+    /// replace the app or a container added before.
+    pub fn check_service_name(&self, name: &str) -> Result<(), String> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(format!(
+                "Invalid service name {:?}, use letters, digits, '-', '_' and '.'",
+                name
+            ));
+        }
+        if name == "app" {
+            return Err("Service app is the project itself, pick another name".into());
+        }
+        if self.services.iter().any(|service| service.name == name) {
+            return Err(format!("Service {} already exists", name));
+        }
+        Ok(())
+    }
+
+    /// Config, env and scaffolded files `init` writes once and then leaves to the user, as
+    /// opposed to the [`artifacts`](Self::artifacts) every run regenerates.
+    pub fn seeds(&self) -> Vec<String> {
+        let mut files = vec![];
+        if self.config != "none" {
+            files.extend(
+                std::iter::once("development")
+                    .chain(self.environments.iter().map(String::as_str))
+                    .map(|environment| format!("config.{}.{}", environment, self.config)),
+            );
+        }
+        if self.env {
+            files.push(".env".into());
+            files.push(".env.example".into());
+            if self.env_secrets {
+                files.extend(
+                    utils::SENSITIVE_VARS
+                        .iter()
+                        .map(|(name, _)| format!("secrets/{}", name.to_lowercase())),
+                );
+            }
+        }
+        files.extend(
+            self.environments
+                .iter()
+                .map(|environment| format!(".env.{}", environment)),
+        );
+        if self.scaffold {
+            let options = self.options();
+            files.extend(
+                crate::languages::scaffold(
+                    &self.language,
+                    &self.name,
+                    options.health.as_ref(),
+                    options.config,
+                )
+                .into_iter()
+                .map(|(file, _)| file.to_string()),
+            );
+        }
+        files
+    }
+
+    /// Files rendered from templates, as opposed to config, env and scaffolded sources.
+    pub fn artifacts(&self) -> Vec<String> {
+        let mut files = vec![
+            "Dockerfile".to_string(),
+            "docker-compose.yaml".to_string(),
+            utils::DEV_COMPOSE_FILE.to_string(),
+            "nodemon.json".to_string(),
+        ];
+        files.extend(
+            self.environments
+                .iter()
+                .map(|environment| format!("docker-compose.{}.yaml", environment)),
+        );
+        if self.config != "none" {
+            files.push(crate::config::SCHEMA_FILE.to_string());
+        }
+        match self.runner.as_deref() {
+            Some("make") => files.push("Makefile".into()),
+            Some("just") => files.push("justfile".into()),
+            Some("task") => files.push("Taskfile.yml".into()),
+            _ => {}
+        }
+        if self.bake {
+            files.push("docker-bake.hcl".into());
+        }
+        if self.debug {
+            files.push(utils::DEBUG_COMPOSE_FILE.into());
+            files.push(".vscode/launch.json".into());
+        }
+        if self.devcontainer {
+            files.push(".devcontainer/devcontainer.json".into());
+        }
+        files.extend(
+            self.ci
+                .iter()
+                .filter_map(|provider| utils::ci_file(provider))
+                .map(String::from),
+        );
+        if self.k8s {
+            files.extend(
+                crate::k8s::files(self.config != "none")
+                    .into_iter()
+                    .map(String::from),
+            );
+        }
+        files
+    }
+}
+
+/// Defaults for the `add service` kinds DockerForge knows about.
+struct KnownService {
+    kind: &'static str,
+    name: &'static str,
+    image: &'static str,
+    port: u16,
+    environment: &'static [(&'static str, &'static str)],
+    data: &'static str,
+}
+
+const KNOWN_SERVICES: [KnownService; 5] = [
+    KnownService {
+        kind: "postgres",
+        name: "db",
+        image: "postgres:16-alpine",
+        port: 5432,
+        environment: &[
+            ("POSTGRES_USER", "app"),
+            ("POSTGRES_PASSWORD", "app"),
+            ("POSTGRES_DB", "app_development"),
+        ],
+        data: "/var/lib/postgresql/data",
+    },
+    KnownService {
+        kind: "mysql",
+        name: "db",
+        image: "mysql:8",
+        port: 3306,
+        environment: &[
+            ("MYSQL_USER", "app"),
+            ("MYSQL_PASSWORD", "app"),
+            ("MYSQL_DATABASE", "app_development"),
+            ("MYSQL_RANDOM_ROOT_PASSWORD", "yes"),
+        ],
+        data: "/var/lib/mysql",
+    },
+    KnownService {
+        kind: "redis",
+        name: "redis",
+        image: "redis:7-alpine",
+        port: 6379,
+        environment: &[],
+        data: "/data",
+    },
+    KnownService {
+        kind: "mongo",
+        name: "mongo",
+        image: "mongo:7",
+        port: 27017,
+        environment: &[],
+        data: "/data/db",
+    },
+    KnownService {
+        kind: "rabbitmq",
+        name: "rabbitmq",
+        image: "rabbitmq:3-management-alpine",
+        port: 5672,
+        environment: &[],
+        data: "/var/lib/rabbitmq",
+    },
+];
+
+impl ExtraService {
+    /// Builds a service from a known kind or, for any other kind, from `image`.
+    pub fn new(
+        kind: &str,
+        name: Option<String>,
+        image: Option<String>,
+        port: Option<u16>,
+    ) -> Option<Self> {
+        match KNOWN_SERVICES.iter().find(|known| known.kind == kind) {
+            Some(known) => Some(ExtraService {
+                name: name.unwrap_or(known.name.to_string()),
+                image: image.unwrap_or(known.image.to_string()),
+                port: port.or(Some(known.port)),
+                environment: known
+                    .environment
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+                data: Some(known.data.to_string()),
+                sidecar: false,
+            }),
+            None => Some(ExtraService {
+                name: name.unwrap_or(kind.to_string()),
+                image: image?,
+                port,
+                environment: BTreeMap::new(),
+                data: None,
+                sidecar: false,
+            }),
+        }
+    }
+
+    /// Sidecars are always taken as given, never from the known service kinds.
+    pub fn sidecar(name: &str, image: &str) -> Self {
+        ExtraService {
+            name: name.to_string(),
+            image: image.to_string(),
+            port: None,
+            environment: BTreeMap::new(),
+            data: None,
+            sidecar: true,
+        }
+    }
+}
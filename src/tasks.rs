@@ -0,0 +1,434 @@
+use serde_yaml::{Mapping, Value as Yaml};
+
+/// Tasks every runner may define, which an environment's own task must not shadow.
+pub const BUILT_IN: &[&str] = &[
+    "help",
+    "default",
+    "start",
+    "stop",
+    "logs",
+    "shell",
+    "build",
+    "push",
+    "remove",
+    "update",
+    "clean",
+    "debug",
+    "bake",
+    "bake-push",
+    "bake-test",
+    "test",
+    "lint",
+    "run",
+];
+
+/// Checks environment names, which become task names and the
+/// `docker-compose.<env>.yaml` and `.env.<env>` file names.
+pub fn check_environments(environments: &[String]) -> Result<(), String> {
+    for (index, environment) in environments.iter().enumerate() {
+        if environment.is_empty()
+            || !environment
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "Invalid environment {:?}, use lowercase letters, digits, '-' and '_'",
+                environment
+            ));
+        }
+        if BUILT_IN.contains(&environment.as_str()) {
+            return Err(format!(
+                "Environment {} collides with the built-in {} task",
+                environment, environment
+            ));
+        }
+        // Development is the base stack, and the others are the names of the
+        // dev overlay and the env template.
+        if ["development", "override", "example"].contains(&environment.as_str()) {
+            return Err(format!("Environment {} is reserved", environment));
+        }
+        if environments[..index].contains(environment) {
+            return Err(format!("Environment {} is listed twice", environment));
+        }
+    }
+    Ok(())
+}
+
+pub struct Task {
+    pub name: String,
+    pub description: String,
+    pub dependencies: Vec<String>,
+    pub commands: Vec<String>,
+}
+
+pub enum Value {
+    Literal(String),
+    Shell(&'static str),
+    /// `REGISTRY/IMAGE:TAG`, dropping the registry prefix when it is empty.
+    ImageRef,
+}
+
+/// Task model shared by the Makefile, justfile and Taskfile renderers.
+///
+/// Commands reference variables with make syntax (`$(NAME)`), which each
+/// renderer rewrites into its own interpolation syntax.
+pub struct Tasks {
+    pub exports: Vec<(&'static str, &'static str)>,
+    pub variables: Vec<(&'static str, Value)>,
+    pub tasks: Vec<Task>,
+}
+
+impl Task {
+    fn new(name: &str, description: &str, commands: &[&str]) -> Self {
+        Task {
+            name: name.to_string(),
+            description: description.to_string(),
+            dependencies: vec![],
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    fn after(mut self, dependencies: &[&str]) -> Self {
+        self.dependencies = dependencies.iter().map(|d| d.to_string()).collect();
+        self
+    }
+}
+
+impl Tasks {
+    pub fn new(name: &str, language: Option<&str>, options: &crate::utils::Options) -> Self {
+        let exports = if options.buildkit {
+            vec![("DOCKER_BUILDKIT", "1"), ("COMPOSE_DOCKER_CLI_BUILD", "1")]
+        } else {
+            vec![]
+        };
+        let variables = vec![
+            ("REGISTRY", Value::Literal(String::new())),
+            ("IMAGE", Value::Literal(format!("{}-app", name))),
+            (
+                "TAG",
+                Value::Shell("git describe --tags --always --dirty 2>/dev/null || echo latest"),
+            ),
+            ("COMPOSE", Value::Literal("docker compose".into())),
+            (
+                "REVISION",
+                Value::Shell("git rev-parse HEAD 2>/dev/null || echo unknown"),
+            ),
+            ("CREATED", Value::Shell("date -u +%Y-%m-%dT%H:%M:%SZ")),
+            ("IMAGE_REF", Value::ImageRef),
+        ];
+
+        let (run, test, lint) = match language.unwrap_or_default() {
+            "Typescript" => ("npx ts-node src/index.ts", "npm test", "npx eslint src"),
+            "Javascript" => ("node src/index.js", "npm test", "npx eslint src"),
+            "Python" => (
+                "python src/index.py",
+                "python -m pytest",
+                "python -m ruff check src",
+            ),
+            "Rust" => ("cargo run", "cargo test", "cargo clippy -- -D warnings"),
+            "Golang" => ("go run src/main.go", "go test ./...", "go vet ./..."),
+            _ => ("", "", ""),
+        };
+
+        // Forwards the registry credentials the Dockerfile's secret mounts expect.
+        let secret = match language.and_then(crate::languages::registry_secret) {
+            Some((id, source)) if options.private_registry => {
+                format!(" --secret id={},src={}", id, source)
+            }
+            _ => String::new(),
+        };
+
+        let mut tasks = vec![
+            Task::new(
+                "start",
+                "Start the development stack",
+                &["$(COMPOSE) up -d"],
+            ),
+            Task::new(
+                "stop",
+                "Stop the stack and remove volumes",
+                &["$(COMPOSE) down -v"],
+            ),
+            Task::new("logs", "Follow the app logs", &["$(COMPOSE) logs -f app"]),
+            Task::new(
+                "shell",
+                "Open a shell in the app container",
+                &["$(COMPOSE) exec app sh"],
+            ),
+            Task::new(
+                "build",
+                "Build the production image",
+                &[&format!(
+                    "docker build --target production{} --build-arg REVISION=$(REVISION) --build-arg CREATED=$(CREATED) -t $(IMAGE_REF) .",
+                    secret
+                )],
+            ),
+            Task::new(
+                "push",
+                "Push the image to the registry",
+                &["docker push $(IMAGE_REF)"],
+            )
+            .after(&["build"]),
+            Task::new(
+                "remove",
+                "Remove the built image",
+                &["docker image rm $(IMAGE_REF)"],
+            ),
+            Task::new("update", "Rebuild and restart the stack", &[])
+                .after(&["stop", "remove", "start"]),
+            Task::new(
+                "clean",
+                "Remove containers, volumes and local images",
+                &["$(COMPOSE) down -v --rmi local --remove-orphans"],
+            ),
+        ];
+        if options.debug {
+            tasks.push(Task::new(
+                "debug",
+                "Start the development stack with the debugger port open",
+                &[&format!(
+                    "$(COMPOSE) -f docker-compose.yaml -f {} -f {} up -d",
+                    crate::utils::DEV_COMPOSE_FILE,
+                    crate::utils::DEBUG_COMPOSE_FILE
+                )],
+            ));
+        }
+        for environment in &options.environments {
+            tasks.push(Task::new(
+                environment,
+                &format!("Start the stack with the {} overlay", environment),
+                &[&format!(
+                    "$(COMPOSE) -f docker-compose.yaml -f docker-compose.{}.yaml up -d",
+                    environment
+                )],
+            ));
+        }
+        if options.bake {
+            let bake = "REGISTRY=$(REGISTRY) IMAGE=$(IMAGE) TAG=$(TAG) REVISION=$(REVISION) CREATED=$(CREATED) docker buildx bake";
+            tasks.push(Task::new(
+                "bake",
+                "Build the multi-platform production image with buildx bake",
+                &[bake],
+            ));
+            tasks.push(Task::new(
+                "bake-push",
+                "Build and push the multi-platform production image",
+                &[&format!("{} --push production", bake)],
+            ));
+            tasks.push(Task::new(
+                "bake-test",
+                "Run the test stage with buildx bake",
+                &[&format!("{} test", bake)],
+            ));
+        }
+        if !test.is_empty() {
+            tasks.push(Task::new(
+                "test",
+                "Run the tests inside the app container",
+                &[&format!("$(COMPOSE) run --rm app {}", test)],
+            ));
+            tasks.push(Task::new(
+                "lint",
+                "Run the linter inside the app container",
+                &[&format!("$(COMPOSE) run --rm app {}", lint)],
+            ));
+            tasks.push(Task::new("run", "Run the app on the host", &[run]));
+        }
+
+        Tasks {
+            exports,
+            variables,
+            tasks,
+        }
+    }
+
+    /// Rewrites `$(NAME)` references to the given runner's interpolation syntax.
+    fn interpolate(&self, command: &str, reference: fn(&str) -> String) -> String {
+        self.variables
+            .iter()
+            .fold(command.to_string(), |command, (name, _)| {
+                command.replace(&format!("$({})", name), &reference(name))
+            })
+    }
+
+    pub fn makefile(&self) -> String {
+        let mut contents = String::from(".DEFAULT_GOAL := help\n\n");
+        for (name, value) in &self.exports {
+            contents.push_str(&format!("export {} = {}\n", name, value));
+        }
+        for (name, value) in &self.variables {
+            let value = match value {
+                Value::Literal(literal) => literal.clone(),
+                Value::Shell(command) => format!("$(shell {})", command),
+                Value::ImageRef => "$(if $(REGISTRY),$(REGISTRY)/,)$(IMAGE):$(TAG)".into(),
+            };
+            contents.push_str(&format!("{} ?= {}\n", name, value));
+        }
+
+        let names: Vec<&str> = self.tasks.iter().map(|task| task.name.as_str()).collect();
+        contents.push_str(&format!("\n.PHONY: help {}\n\n", names.join(" ")));
+        contents.push_str("help: ## Show this help\n");
+        contents.push_str(
+            "\t@grep -E '^[a-zA-Z0-9_.-]+:.*?## ' $(MAKEFILE_LIST) | awk 'BEGIN {FS = \":.*?## \"}; {printf \"\\033[36m%-12s\\033[0m %s\\n\", $$1, $$2}'\n",
+        );
+
+        for task in &self.tasks {
+            contents.push_str(&format!(
+                "\n{}:{} ## {}\n",
+                task.name,
+                task.dependencies
+                    .iter()
+                    .map(|dependency| format!(" {}", dependency))
+                    .collect::<String>(),
+                task.description
+            ));
+            for command in &task.commands {
+                contents.push_str(&format!("\t{}\n", command));
+            }
+        }
+
+        contents
+    }
+
+    pub fn justfile(&self) -> String {
+        let mut contents = String::new();
+        for (name, value) in &self.exports {
+            contents.push_str(&format!("export {} := \"{}\"\n", name, value));
+        }
+        for (name, value) in &self.variables {
+            let value = match value {
+                Value::Literal(literal) => {
+                    format!("env_var_or_default(\"{}\", \"{}\")", name, literal)
+                }
+                Value::Shell(command) => format!("env_var_or_default(\"{}\", `{}`)", name, command),
+                Value::ImageRef => "if REGISTRY == \"\" { IMAGE + \":\" + TAG } else { REGISTRY + \"/\" + IMAGE + \":\" + TAG }".into(),
+            };
+            contents.push_str(&format!("{} := {}\n", name, value));
+        }
+
+        contents.push_str("\n# Show this help\ndefault:\n    @just --list\n");
+        for task in &self.tasks {
+            contents.push_str(&format!(
+                "\n# {}\n{}:{}\n",
+                task.description,
+                task.name,
+                task.dependencies
+                    .iter()
+                    .map(|dependency| format!(" {}", dependency))
+                    .collect::<String>()
+            ));
+            for command in &task.commands {
+                let command = self.interpolate(command, |name| format!("{{{{{}}}}}", name));
+                contents.push_str(&format!("    {}\n", command));
+            }
+        }
+
+        contents
+    }
+
+    pub fn taskfile(&self) -> String {
+        let mut taskfile = Mapping::new();
+        taskfile.insert("version".into(), "3".into());
+
+        if !self.exports.is_empty() {
+            let env: Mapping = self
+                .exports
+                .iter()
+                .map(|(name, value)| ((*name).into(), (*value).into()))
+                .collect();
+            taskfile.insert("env".into(), env.into());
+        }
+
+        let vars: Mapping = self
+            .variables
+            .iter()
+            .map(|(name, value)| {
+                let value: Yaml = match value {
+                    Value::Literal(literal) => literal.as_str().into(),
+                    Value::Shell(command) => {
+                        Mapping::from_iter([("sh".into(), (*command).into())]).into()
+                    }
+                    Value::ImageRef => {
+                        "{{if .REGISTRY}}{{.REGISTRY}}/{{end}}{{.IMAGE}}:{{.TAG}}".into()
+                    }
+                };
+                ((*name).into(), value)
+            })
+            .collect();
+        taskfile.insert("vars".into(), vars.into());
+
+        let mut tasks = Mapping::new();
+        tasks.insert(
+            "default".into(),
+            Mapping::from_iter([
+                ("desc".into(), "Show this help".into()),
+                ("silent".into(), true.into()),
+                ("cmds".into(), vec![Yaml::from("task --list")].into()),
+            ])
+            .into(),
+        );
+        for task in &self.tasks {
+            // Dependencies run as sequential sub-tasks, since Taskfile `deps` run in parallel.
+            let mut cmds: Vec<Yaml> = task
+                .dependencies
+                .iter()
+                .map(|dependency| {
+                    Mapping::from_iter([("task".into(), dependency.as_str().into())]).into()
+                })
+                .collect();
+            cmds.extend(
+                task.commands
+                    .iter()
+                    .map(|command| self.interpolate(command, |name| format!("{{{{.{}}}}}", name)))
+                    .map(Yaml::from),
+            );
+            tasks.insert(
+                task.name.as_str().into(),
+                Mapping::from_iter([
+                    ("desc".into(), task.description.as_str().into()),
+                    ("cmds".into(), cmds.into()),
+                ])
+                .into(),
+            );
+        }
+        taskfile.insert("tasks".into(), tasks.into());
+
+        serde_yaml::to_string(&taskfile).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn environments_never_shadow_a_task() {
+        let options = utils::Options {
+            config: None,
+            buildkit: true,
+            private_registry: false,
+            hardened: false,
+            host_user: None,
+            health: None,
+            debug: true,
+            bake: true,
+            environments: vec![],
+            env: false,
+            env_secrets: false,
+        };
+        for task in Tasks::new("app", Some("Rust"), &options).tasks {
+            assert!(BUILT_IN.contains(&task.name.as_str()), "{}", task.name);
+            assert!(check_environments(&[task.name]).is_err());
+        }
+        for environment in ["development", "override", "example", "Prod", "eu 1", ""] {
+            assert!(
+                check_environments(&[environment.into()]).is_err(),
+                "{}",
+                environment
+            );
+        }
+        assert!(check_environments(&["staging".into(), "staging".into()]).is_err());
+        assert!(check_environments(&["staging".into(), "eu-1".into(), "prod_2".into()]).is_ok());
+    }
+}
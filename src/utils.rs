@@ -1,31 +1,424 @@
 use serde::{Deserialize, Serialize};
-use std::fs::{create_dir, File};
+use std::collections::BTreeMap;
+use std::fs::{create_dir, create_dir_all, File};
 use std::io::Write;
-use std::path::PathBuf;
 
+pub const DOCKERFILE_SYNTAX: &str = "# syntax=docker/dockerfile:1\n";
+pub const DEV_USER: &str = "ARG UID=1000\nARG GID=1000\nRUN (getent group ${GID} || addgroup -g ${GID} dev) \\
+    && (getent passwd ${UID} || adduser -D -u ${UID} -G \"$(getent group ${GID} | cut -d: -f1)\" dev) \\
+    && chown -R ${UID}:${GID} /app\n";
+pub const NON_ROOT_USER: &str = "RUN addgroup -S app && adduser -S -G app app\nUSER app\n";
+pub const APP_PORT: u16 = 8080;
+
+pub struct Options<'a> {
+    pub config: Option<&'a str>,
+    pub buildkit: bool,
+    pub private_registry: bool,
+    pub hardened: bool,
+    pub host_user: Option<(u32, u32)>,
+    pub health: Option<Health>,
+    pub debug: bool,
+    pub bake: bool,
+    pub environments: Vec<String>,
+    pub env: bool,
+    pub env_secrets: bool,
+}
+
+/// Application settings that should never be written in plain text to `.env`.
+pub const SENSITIVE_VARS: &[(&str, &str)] = &[("SECRET_KEY", "Application secret key")];
+
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+    pub placeholder: String,
+    pub comment: String,
+}
+
+pub enum Health {
+    Http { port: u16, path: String },
+    Tcp { port: u16 },
+    Command(String),
+}
+
+impl Health {
+    /// Parses `[PORT]/PATH` as an HTTP check, `PORT` as a TCP check and anything else as a command.
+    pub fn parse(value: &str) -> Self {
+        if let Some(index) = value.find('/') {
+            let (port, path) = value.split_at(index);
+            if port.is_empty() {
+                return Health::Http {
+                    port: APP_PORT,
+                    path: path.to_string(),
+                };
+            }
+            if let Ok(port) = port.parse() {
+                return Health::Http {
+                    port,
+                    path: path.to_string(),
+                };
+            }
+        }
+        match value.parse() {
+            Ok(port) => Health::Tcp { port },
+            Err(_) => Health::Command(value.to_string()),
+        }
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        match self {
+            Health::Http { port, .. } | Health::Tcp { port } => Some(*port),
+            Health::Command(_) => None,
+        }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Health::Http { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Check command relying only on the busybox tools shipped with alpine images.
+    pub fn busybox_command(&self) -> String {
+        match self {
+            Health::Http { port, path } => {
+                format!(
+                    "wget -q --spider http://localhost:{}{} || exit 1",
+                    port, path
+                )
+            }
+            Health::Tcp { port } => format!("nc -z localhost {} || exit 1", port),
+            Health::Command(command) => command.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Metadata {
+    pub title: String,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub license: Option<String>,
+    pub source: Option<String>,
+}
+
+impl Metadata {
+    /// Fills the source from the git repository in the current directory.
+    pub fn with_git(mut self) -> Self {
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+                .filter(|output| !output.is_empty())
+        };
+        if self.source.is_none() {
+            self.source = git(&["config", "--get", "remote.origin.url"]).map(|url| {
+                let url = url.trim_end_matches(".git");
+                match url.strip_prefix("git@") {
+                    Some(ssh) => format!("https://{}", ssh.replacen(':', "/", 1)),
+                    None => url.to_string(),
+                }
+            });
+        }
+        self
+    }
+
+    /// Runtime stage `ARG`/`LABEL` block with the `org.opencontainers.image.*` annotations.
+    pub fn labels(&self) -> String {
+        let mut labels = vec![("title", self.title.clone())];
+        for (key, value) in [
+            ("version", &self.version),
+            ("description", &self.description),
+            ("licenses", &self.license),
+            ("source", &self.source),
+        ] {
+            if let Some(value) = value {
+                labels.push((key, value.clone()));
+            }
+        }
+        labels.push(("revision", "${REVISION}".into()));
+        labels.push(("created", "${CREATED}".into()));
+
+        format!(
+            "ARG REVISION=unknown\nARG CREATED=unknown\nLABEL {}\n",
+            labels
+                .iter()
+                .map(|(key, value)| format!(
+                    "org.opencontainers.image.{}=\"{}\"",
+                    key,
+                    value.replace('"', "\\\"")
+                ))
+                .collect::<Vec<String>>()
+                .join(" \\\n      ")
+        )
+    }
+}
+
+/// Example settings written to the config skeleton and read back by the scaffolds.
 #[derive(Serialize, Deserialize)]
-pub struct Compose {
-    pub version: &'static str,
-    pub services: std::collections::HashMap<String, Service>,
+pub struct AppConfig {
+    pub app: AppSettings,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AppSettings {
+    pub port: u16,
+    pub log_level: String,
+    pub database_url: String,
+}
+
+impl AppConfig {
+    pub fn new(environment: &str, port: u16) -> Self {
+        AppConfig {
+            app: AppSettings {
+                port,
+                log_level: if environment == "development" {
+                    "debug".into()
+                } else {
+                    "info".into()
+                },
+                database_url: format!("postgres://app:app@db:5432/app_{}", environment),
+            },
+        }
+    }
+
+    pub fn render(&self, format: &str) -> String {
+        match format {
+            "json" => serde_json::to_string_pretty(self).unwrap() + "\n",
+            "yaml" => serde_yaml::to_string(self).unwrap(),
+            "toml" => toml::to_string(self).unwrap(),
+            "ini" => format!(
+                "[app]\nport = {}\nlog_level = {}\ndatabase_url = {}\n",
+                self.app.port, self.app.log_level, self.app.database_url
+            ),
+            _ => String::new(),
+        }
+    }
+}
+
+pub fn read_file(name: &str) -> Option<String> {
+    let mut file_path = std::env::current_dir().ok()?;
+    file_path.push(name);
+    std::fs::read_to_string(file_path).ok()
+}
+
+/// Compose file kept as an ordered mapping, so an existing file can be read back with the
+/// services and keys DockerForge doesn't manage left intact and in place.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(transparent)]
+pub struct Compose(pub serde_yaml::Mapping);
+
+impl Compose {
+    pub fn new(
+        services: std::collections::HashMap<String, Service>,
+        secrets: BTreeMap<String, Secret>,
+        configs: BTreeMap<String, ConfigFile>,
+        volumes: BTreeMap<String, Volume>,
+    ) -> Self {
+        let mut compose = serde_yaml::Mapping::new();
+        let mut section = |key: &str, value: serde_yaml::Value| {
+            if value
+                .as_mapping()
+                .is_some_and(|entries| !entries.is_empty())
+            {
+                compose.insert(key.into(), value);
+            }
+        };
+        section("services", serde_yaml::to_value(services).unwrap());
+        section("secrets", serde_yaml::to_value(secrets).unwrap());
+        section("configs", serde_yaml::to_value(configs).unwrap());
+        section("volumes", serde_yaml::to_value(volumes).unwrap());
+        Compose(compose)
+    }
+
+    pub fn parse(contents: &str) -> Option<Self> {
+        serde_yaml::from_str(contents).ok()
+    }
+
+    /// Adds or replaces the entries of `generated` (the `app` service, added services, secrets,
+    /// configs and volumes), keeping every other service and key where it was.
+    pub fn merge(&mut self, generated: Compose) {
+        for (key, value) in generated.0 {
+            match (self.0.get_mut(&key), value) {
+                (
+                    Some(serde_yaml::Value::Mapping(existing)),
+                    serde_yaml::Value::Mapping(entries),
+                ) => {
+                    for (name, entry) in entries {
+                        existing.insert(name, entry);
+                    }
+                }
+                (_, value) => {
+                    self.0.insert(key, value);
+                }
+            }
+        }
+    }
+
+    pub fn render(&self) -> String {
+        serde_yaml::to_string(self).unwrap()
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Service {
-    pub container_name: String,
     pub build: Build,
     pub command: String,
-    pub volumes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub configs: Vec<ConfigMount>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
     pub restart: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub env_file: Vec<EnvFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deploy: Option<Deploy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub healthcheck: Option<Healthcheck>,
+    #[serde(flatten)]
+    pub security: Security,
+}
+
+/// Long `env_file` syntax. The `.env` files are gitignored, so a fresh clone must still start.
+#[derive(Serialize, Deserialize)]
+pub struct EnvFile {
+    pub path: String,
+    pub required: bool,
+}
+
+impl EnvFile {
+    pub fn optional(path: &str) -> Self {
+        EnvFile {
+            path: path.to_string(),
+            required: false,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Build {
     pub context: &'static str,
     pub target: &'static str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Deploy {
+    pub replicas: u32,
+    pub resources: Resources,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Resources {
+    pub limits: Limits,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Limits {
+    pub cpus: String,
+    pub memory: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Healthcheck {
+    pub test: Vec<String>,
+    pub interval: &'static str,
+    pub timeout: &'static str,
+    pub start_period: &'static str,
+    pub retries: u32,
+}
+
+impl Healthcheck {
+    pub fn new(command: &str) -> Self {
+        Healthcheck {
+            test: vec!["CMD-SHELL".into(), command.to_string()],
+            interval: "30s",
+            timeout: "5s",
+            start_period: "10s",
+            retries: 3,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Security {
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub read_only: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub cap_add: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub cap_drop: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub security_opt: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tmpfs: Vec<String>,
+}
+
+impl Security {
+    pub fn new(hardened: bool) -> Self {
+        if !hardened {
+            return Security::default();
+        }
+        Security {
+            read_only: true,
+            cap_drop: vec!["ALL".into()],
+            security_opt: vec!["no-new-privileges:true".into()],
+            tmpfs: vec!["/tmp".into()],
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Secret {
+    pub file: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Volume {}
+
+#[derive(Serialize, Deserialize)]
+pub struct ConfigFile {
+    pub file: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ConfigMount {
+    pub source: String,
+    pub target: String,
+}
+
+/// Default restart policy and deploy settings for an environment overlay.
+pub fn environment(name: &str) -> (&'static str, Deploy) {
+    let (restart, cpus, memory) = match name {
+        "production" => ("always", "1.0", "1G"),
+        "staging" => ("unless-stopped", "0.5", "512M"),
+        _ => ("on-failure", "0.5", "256M"),
+    };
+    (
+        restart,
+        Deploy {
+            replicas: 1,
+            resources: Resources {
+                limits: Limits {
+                    cpus: cpus.into(),
+                    memory: memory.into(),
+                },
+            },
+        },
+    )
 }
 
 pub fn create_file(name: &str, contents: &str) {
-    let mut file_path = PathBuf::from(std::env::current_dir().unwrap());
+    let mut file_path = std::env::current_dir().unwrap();
     file_path.push(name);
 
     let mut file = File::create(file_path).unwrap();
@@ -33,7 +426,7 @@ pub fn create_file(name: &str, contents: &str) {
 }
 
 pub fn create_src_dir() -> bool {
-    let mut file_path = PathBuf::from(std::env::current_dir().unwrap());
+    let mut file_path = std::env::current_dir().unwrap();
     file_path.push("src");
 
     if file_path.exists() {
@@ -46,52 +439,702 @@ pub fn create_src_dir() -> bool {
     }
 }
 
-pub fn write_env() {
-    create_file(".env", "PARAM=123");
+/// Uid/gid of the user running DockerForge, so bind mounted files keep their owner. Under
+/// `sudo` this is the invoking user rather than root.
+pub fn host_user() -> Option<(u32, u32)> {
+    #[cfg(unix)]
+    {
+        // SAFETY: getuid and getgid cannot fail and have no preconditions.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        if uid != 0 {
+            return Some((uid, gid));
+        }
+        std::env::var_os("SUDO_USER")?;
+        let sudo_id = |var: &str| std::env::var(var).ok()?.parse().ok();
+        if let (Some(uid), Some(gid)) = (sudo_id("SUDO_UID"), sudo_id("SUDO_GID")) {
+            return Some((uid, gid)).filter(|(uid, _)| *uid != 0);
+        }
+        use std::os::unix::fs::MetadataExt;
+        let metadata = std::fs::metadata(std::env::current_dir().ok()?).ok()?;
+        Some((metadata.uid(), metadata.gid())).filter(|(uid, _)| *uid != 0)
+    }
+    #[cfg(not(unix))]
+    None
+}
+
+pub fn healthcheck_instruction(command: &str) -> String {
+    format!(
+        "HEALTHCHECK --interval=30s --timeout=5s --start-period=10s --retries=3 \\\n    CMD {}\n",
+        command
+    )
+}
+
+/// Dev-only overlay, loaded by a plain `docker compose` next to `docker-compose.yaml` but not by
+/// the environment stacks, which name their files with `-f`.
+pub const DEV_COMPOSE_FILE: &str = "docker-compose.override.yaml";
+
+/// Development overlay for the `app` service: the project bind mounted as the `/app` workspace,
+/// with the named cache `volumes` on top, run as the host user so bind mounted files keep their
+/// owner.
+pub fn dev_compose(host_user: Option<(u32, u32)>, volumes: &[(&str, &str)]) -> String {
+    let (mounts, declarations) = named_volumes(volumes);
+    let mut app_volumes = vec![".:/app".to_string()];
+    app_volumes.extend(mounts);
+    let mut app = serde_json::json!({ "volumes": app_volumes });
+    if let Some((uid, gid)) = host_user {
+        app["build"] = serde_json::json!({
+            "args": {
+                "UID": format!("${{UID:-{}}}", uid),
+                "GID": format!("${{GID:-{}}}", gid),
+            },
+        });
+        app["user"] = serde_json::json!(format!("${{UID:-{}}}:${{GID:-{}}}", uid, gid));
+    }
+    let mut compose = serde_json::json!({ "services": { "app": app } });
+    if !declarations.is_empty() {
+        compose["volumes"] = serde_json::to_value(declarations).unwrap();
+    }
+    serde_yaml::to_string(&compose).unwrap()
+}
+
+/// Dev-only overlay publishing the debugger port, kept out of the base compose file so the
+/// environment stacks (`-f docker-compose.yaml -f docker-compose.<env>.yaml`) never inherit it.
+pub const DEBUG_COMPOSE_FILE: &str = "docker-compose.debug.yaml";
+
+/// Debugger overlay for the `app` service, replacing its command unless `command` is empty.
+/// Native debuggers (delve, gdbserver) also need ptrace.
+pub fn debug_compose(port: u16, ptrace: bool, command: &[&str]) -> String {
+    let mut app = serde_json::json!({ "ports": [format!("{}:{}", port, port)] });
+    if !command.is_empty() {
+        app["command"] = serde_json::json!(command);
+    }
+    if ptrace {
+        app["cap_add"] = serde_json::json!(["SYS_PTRACE"]);
+        app["security_opt"] = serde_json::json!(["seccomp:unconfined"]);
+    }
+    serde_yaml::to_string(&serde_json::json!({ "services": { "app": app } })).unwrap()
 }
 
-pub fn write_config(config_type: &str) {
-    let contents = String::from(match config_type {
-        "json" => "{\n\t\"setup\":{}\n}",
-        "yaml" => "setup:",
-        "ini" | "toml" => "[setup]\n",
-        _ => "",
+pub fn write_launch(launch: &str) {
+    let mut dir_path = std::env::current_dir().unwrap();
+    dir_path.push(".vscode");
+    create_dir_all(dir_path).unwrap();
+    create_file(".vscode/launch.json", launch);
+}
+
+pub fn devcontainer(
+    name: &str,
+    debug: bool,
+    extensions: &[&str],
+    ports: &[u16],
+    post_create: &str,
+) -> String {
+    let mut compose_files = vec![
+        "../docker-compose.yaml".to_string(),
+        format!("../{}", DEV_COMPOSE_FILE),
+    ];
+    if debug {
+        compose_files.push(format!("../{}", DEBUG_COMPOSE_FILE));
+    }
+    let devcontainer = serde_json::json!({
+        "name": name,
+        "dockerComposeFile": compose_files,
+        "service": "app",
+        "workspaceFolder": "/app",
+        "shutdownAction": "stopCompose",
+        "customizations": {
+            "vscode": {
+                "extensions": extensions,
+            },
+        },
+        "forwardPorts": ports,
+        "postCreateCommand": post_create,
     });
 
-    create_file(&format!("config.{}", config_type), &contents);
+    serde_json::to_string_pretty(&devcontainer).unwrap()
+}
+
+pub fn write_devcontainer(devcontainer: &str) {
+    let mut dir_path = std::env::current_dir().unwrap();
+    dir_path.push(".devcontainer");
+    create_dir_all(dir_path).unwrap();
+    create_file(".devcontainer/devcontainer.json", devcontainer);
+}
+
+/// Mounts `config.{environment}.{format}` as `/app/config.{format}` through compose `configs`.
+///
+/// Every compose file uses the same `app_config` name, so an environment overlay
+/// replaces the development file instead of adding a second mount.
+pub fn app_config(
+    config: Option<&str>,
+    environment: &str,
+) -> (Vec<ConfigMount>, BTreeMap<String, ConfigFile>) {
+    match config {
+        Some(conf) => (
+            vec![ConfigMount {
+                source: "app_config".into(),
+                target: format!("/app/config.{}", conf),
+            }],
+            BTreeMap::from([(
+                "app_config".to_string(),
+                ConfigFile {
+                    file: format!("./config.{}.{}", environment, conf),
+                },
+            )]),
+        ),
+        None => (vec![], BTreeMap::new()),
+    }
+}
+
+pub fn named_volumes(volumes: &[(&str, &str)]) -> (Vec<String>, BTreeMap<String, Volume>) {
+    let mounts = volumes
+        .iter()
+        .map(|(name, target)| format!("{}:{}", name, target))
+        .collect();
+    let declarations = volumes
+        .iter()
+        .map(|(name, _)| (name.to_string(), Volume::default()))
+        .collect();
+    (mounts, declarations)
+}
+
+pub fn cache_mounts(buildkit: bool, targets: &[&str]) -> String {
+    if !buildkit {
+        return String::new();
+    }
+    targets
+        .iter()
+        .map(|target| format!("--mount=type=cache,target={} ", target))
+        .collect()
+}
+
+pub fn secret_mount(private_registry: bool, id: &str, target: &str) -> String {
+    if !private_registry {
+        return String::new();
+    }
+    format!("--mount=type=secret,id={},target={} ", id, target)
+}
+
+pub fn registry_secrets(private_registry: bool, id: &str, file: &str) -> BTreeMap<String, Secret> {
+    if !private_registry {
+        return BTreeMap::new();
+    }
+    BTreeMap::from([(
+        id.to_string(),
+        Secret {
+            file: file.to_string(),
+        },
+    )])
 }
-pub fn write_makefile(name: &str, language: Option<&str>) {
-    let mut contents: String = String::from("start:\n\tdocker-compose up -d\n");
-    contents.push_str("stop:\n\tdocker-compose down -v\n");
-    contents.push_str(&format!("build:\n\tdocker build -t {}-app .\n", name));
-    contents.push_str(&format!("remove:\n\tdocker image rm {}-app\n", name));
-    contents.push_str("update:\n\tmake stop\n\tmake remove\n\tmake start\n");
-    contents.push_str("production:\n\tdocker-compose -f docker-compose.yaml -f docker-compose.production.yaml up -d\n");
-    if let Some(lang) = language {
-        match lang {
-            "Typescript" => contents.push_str("run:\n\tnpx ts-node src/index.ts\n"),
-            "Javascript" => contents.push_str("run:\n\tnode src/index.js\n"),
-            "Python" => contents.push_str("run:\n\tpython src/index.py\n"),
-            "Rust" => contents.push_str("run:\n\tcargo run\n"),
-            "Golang" => contents.push_str("run:\n\tgo run src/main.go\n"),
-            _ => {}
+
+pub fn app_secrets(env_secrets: bool) -> (Vec<String>, BTreeMap<String, Secret>) {
+    if !env_secrets {
+        return (vec![], BTreeMap::new());
+    }
+    let names: Vec<String> = SENSITIVE_VARS
+        .iter()
+        .map(|(name, _)| name.to_lowercase())
+        .collect();
+    let files = names
+        .iter()
+        .map(|name| {
+            (
+                name.clone(),
+                Secret {
+                    file: format!("./secrets/{}", name),
+                },
+            )
+        })
+        .collect();
+    (names, files)
+}
+
+pub fn env_vars(name: &str, language: &str, options: &Options) -> Vec<EnvVar> {
+    let var = |name: &str, value: String, placeholder: &str, comment: &str| EnvVar {
+        name: name.to_string(),
+        value,
+        placeholder: placeholder.to_string(),
+        comment: comment.to_string(),
+    };
+    let mut vars = vec![var(
+        "COMPOSE_PROJECT_NAME",
+        name.to_string(),
+        name,
+        "Compose project name",
+    )];
+    if options.buildkit {
+        vars.push(var(
+            "DOCKER_BUILDKIT",
+            "1".into(),
+            "1",
+            "Build images with BuildKit",
+        ));
+        vars.push(var(
+            "COMPOSE_DOCKER_CLI_BUILD",
+            "1".into(),
+            "1",
+            "Let docker-compose v1 build through the docker CLI",
+        ));
+    }
+    if let Some((uid, gid)) = options.host_user {
+        vars.push(var(
+            "UID",
+            uid.to_string(),
+            "1000",
+            "Host user id for the development container",
+        ));
+        vars.push(var(
+            "GID",
+            gid.to_string(),
+            "1000",
+            "Host group id for the development container",
+        ));
+    }
+    if options.private_registry && language == "Golang" {
+        vars.push(var(
+            "GOPRIVATE",
+            String::new(),
+            "github.com/your-org/*",
+            "Private Go module path patterns",
+        ));
+    }
+    if let Some(port) = options.health.as_ref().and_then(|h| h.port()) {
+        vars.push(var(
+            "PORT",
+            port.to_string(),
+            &port.to_string(),
+            "Port the application listens on",
+        ));
+    }
+    for (name, comment) in SENSITIVE_VARS {
+        if options.env_secrets {
+            let secret = format!("/run/secrets/{}", name.to_lowercase());
+            vars.push(var(
+                &format!("{}_FILE", name),
+                secret.clone(),
+                &secret,
+                &format!("{} (read from a compose secret)", comment),
+            ));
+        } else {
+            vars.push(var(name, String::new(), "changeme", comment));
+        }
+    }
+    vars
+}
+
+pub fn write_env(name: &str, language: &str, options: &Options) {
+    let vars = env_vars(name, language, options);
+    let env: String = vars
+        .iter()
+        .map(|var| format!("{}={}\n", var.name, var.value))
+        .collect();
+    let example: String = vars
+        .iter()
+        .map(|var| format!("# {}\n{}={}\n", var.comment, var.name, var.placeholder))
+        .collect::<Vec<String>>()
+        .join("\n");
+    create_file(".env", &env);
+    create_file(".env.example", &example);
+
+    if options.env_secrets {
+        let _ = create_dir_all("secrets");
+        for (name, _) in SENSITIVE_VARS {
+            let file = format!("secrets/{}", name.to_lowercase());
+            if read_file(&file).is_none() {
+                create_file(&file, "changeme");
+            }
         }
     }
 
-    create_file("Makefile", &contents);
+    let mut gitignore = read_file(".gitignore").unwrap_or_default();
+    for pattern in [".env", ".env.*", "!.env.example", "secrets/"] {
+        if !gitignore.lines().any(|line| line.trim() == pattern) {
+            if !gitignore.is_empty() && !gitignore.ends_with('\n') {
+                gitignore.push('\n');
+            }
+            gitignore.push_str(pattern);
+            gitignore.push('\n');
+        }
+    }
+    create_file(".gitignore", &gitignore);
 }
 
-pub fn write_dockerfiles(
-    dockerfile: &str,
-    compose_dev: &str,
-    compose_prod: &str,
-    nodemon: Option<&str>,
-) {
-    create_file("Dockerfile", &dockerfile);
-    create_file("docker-compose.yaml", &compose_dev);
-    create_file("docker-compose.production.yaml", &compose_prod);
+pub fn write_config(config_type: &str, environment: &str, port: u16) {
+    let contents = AppConfig::new(environment, port).render(config_type);
+
+    create_file(
+        &format!("config.{}.{}", environment, config_type),
+        &contents,
+    );
+}
+
+pub fn write_tasks(
+    name: &str,
+    language: Option<&str>,
+    options: &Options,
+    runner: &str,
+) -> Option<&'static str> {
+    let tasks = crate::tasks::Tasks::new(name, language, options);
+    let (file, contents) = match runner {
+        "make" => ("Makefile", tasks.makefile()),
+        "just" => ("justfile", tasks.justfile()),
+        "task" => ("Taskfile.yml", tasks.taskfile()),
+        _ => return None,
+    };
+    create_file(file, &contents);
+    Some(file)
+}
+
+/// `docker-bake.hcl` contents, mounting the registry credentials `secret` `(id, source)` into
+/// every target when the project uses a private registry.
+pub fn bake(name: &str, platforms: &[&str], secret: Option<(&str, &str)>) -> String {
+    let platforms = platforms
+        .iter()
+        .map(|platform| format!("\"{}\"", platform))
+        .collect::<Vec<String>>()
+        .join(", ");
+    // Bake only interpolates declared variables, which it fills from the environment.
+    let (home, secret) = match secret {
+        Some((id, source)) => (
+            "variable \"HOME\" {\n  default = null\n}\n\n",
+            format!("  secret = [\"id={},src={}\"]\n", id, source),
+        ),
+        None => ("", String::new()),
+    };
+    format!(
+        r#"{home}variable "REGISTRY" {{
+  default = ""
+}}
+
+variable "IMAGE" {{
+  default = "{name}-app"
+}}
+
+variable "TAG" {{
+  default = "latest"
+}}
+
+variable "REVISION" {{
+  default = "unknown"
+}}
+
+variable "CREATED" {{
+  default = "unknown"
+}}
+
+function "tag" {{
+  params = [version]
+  result = notequal("", REGISTRY) ? "${{REGISTRY}}/${{IMAGE}}:${{version}}" : "${{IMAGE}}:${{version}}"
+}}
+
+group "default" {{
+  targets = ["production"]
+}}
+
+target "_common" {{
+  context = "."
+  dockerfile = "Dockerfile"
+  args = {{
+    REVISION = REVISION
+    CREATED = CREATED
+  }}
+{secret}}}
+
+target "development" {{
+  inherits = ["_common"]
+  target = "development"
+  tags = [tag("dev")]
+  cache-from = ["type=local,src=.buildx-cache/development"]
+  cache-to = ["type=local,dest=.buildx-cache/development,mode=max"]
+}}
+
+target "test" {{
+  inherits = ["_common"]
+  target = "test"
+  output = ["type=cacheonly"]
+  cache-from = ["type=local,src=.buildx-cache/development"]
+}}
+
+target "production" {{
+  inherits = ["_common"]
+  target = "production"
+  platforms = [{platforms}]
+  tags = [tag(TAG), tag("latest")]
+  cache-from = ["type=local,src=.buildx-cache/production"]
+  cache-to = ["type=local,dest=.buildx-cache/production,mode=max"]
+}}
+"#
+    )
+}
+
+pub fn write_bake(name: &str, platforms: &[&str], secret: Option<(&str, &str)>) {
+    create_file("docker-bake.hcl", &bake(name, platforms, secret));
+}
+
+pub fn write_dockerfiles(dockerfile: &str, environments: &[String], nodemon: Option<&str>) {
+    create_file("Dockerfile", dockerfile);
+    for environment in environments {
+        if read_file(&format!(".env.{}", environment)).is_none() {
+            create_file(&format!(".env.{}", environment), "");
+        }
+    }
 
     if let Some(config) = nodemon {
-        create_file("nodemon.json", &config);
+        create_file("nodemon.json", config);
+    }
+}
+
+/// Adds the `add service|sidecar` containers to the development compose file.
+pub fn add_services(compose: &str, services: &[crate::settings::ExtraService]) -> String {
+    if services.is_empty() {
+        return compose.to_string();
+    }
+    let mut compose: serde_yaml::Value = serde_yaml::from_str(compose).unwrap();
+    for service in services {
+        let mut definition = serde_yaml::Mapping::new();
+        definition.insert("image".into(), service.image.as_str().into());
+        if service.sidecar {
+            definition.insert("network_mode".into(), "service:app".into());
+            definition.insert("depends_on".into(), vec!["app"].into());
+        } else {
+            if let Some(port) = service.port {
+                definition.insert("ports".into(), vec![format!("{port}:{port}")].into());
+            }
+            let app = compose["services"]["app"].as_mapping_mut().unwrap();
+            let depends_on = app
+                .entry("depends_on".into())
+                .or_insert(serde_yaml::Sequence::new().into());
+            depends_on
+                .as_sequence_mut()
+                .unwrap()
+                .push(service.name.as_str().into());
+        }
+        if !service.environment.is_empty() {
+            definition.insert(
+                "environment".into(),
+                serde_yaml::to_value(&service.environment).unwrap(),
+            );
+        }
+        if let Some(data) = &service.data {
+            let volume = format!("{}-data", service.name);
+            definition.insert("volumes".into(), vec![format!("{volume}:{data}")].into());
+            if !compose["volumes"].is_mapping() {
+                compose["volumes"] = serde_yaml::Mapping::new().into();
+            }
+            compose["volumes"][volume.as_str()] = serde_yaml::Mapping::new().into();
+        }
+        definition.insert("restart".into(), "unless-stopped".into());
+        compose["services"][service.name.as_str()] = definition.into();
+    }
+    serde_yaml::to_string(&compose).unwrap()
+}
+
+pub fn ci_file(provider: &str) -> Option<&'static str> {
+    match provider {
+        "github" => Some(".github/workflows/docker.yml"),
+        "gitlab" => Some(".gitlab-ci.yml"),
+        _ => None,
+    }
+}
+
+/// Writes a pipeline building and pushing the production image, returning the file written.
+/// The registry credentials `secret` `(id, source)` is read from a CI secret named after its id.
+pub fn write_ci(provider: &str, bake: bool, secret: Option<(&str, &str)>) -> Option<&'static str> {
+    let file = ci_file(provider)?;
+    let mut contents = match provider {
+        "github" => {
+            let build = if bake {
+                r#"      - uses: docker/setup-qemu-action@v3
+      - uses: docker/setup-buildx-action@v3
+      - uses: docker/login-action@v3
+        if: github.event_name != 'pull_request'
+        with:
+          registry: ghcr.io
+          username: ${{ github.actor }}
+          password: ${{ secrets.GITHUB_TOKEN }}
+      - uses: docker/bake-action@v4
+        env:
+          REGISTRY: ghcr.io/${{ github.repository_owner }}
+          TAG: ${{ github.sha }}
+          REVISION: ${{ github.sha }}
+        with:
+          targets: production
+          push: ${{ github.event_name != 'pull_request' }}
+          set: |
+            *.cache-from=type=gha
+            *.cache-to=type=gha,mode=max
+"#
+            } else {
+                r#"      - uses: docker/setup-buildx-action@v3
+      - uses: docker/login-action@v3
+        if: github.event_name != 'pull_request'
+        with:
+          registry: ghcr.io
+          username: ${{ github.actor }}
+          password: ${{ secrets.GITHUB_TOKEN }}
+      - uses: docker/build-push-action@v5
+        with:
+          context: .
+          target: production
+          push: ${{ github.event_name != 'pull_request' }}
+          tags: ghcr.io/${{ github.repository }}:${{ github.sha }}
+          build-args: |
+            REVISION=${{ github.sha }}
+          cache-from: type=gha
+          cache-to: type=gha,mode=max
+"#
+            };
+            format!(
+                r#"name: Docker
+
+on:
+  push:
+    branches: [main]
+    tags: ["v*"]
+  pull_request:
+
+jobs:
+  build:
+    runs-on: ubuntu-latest
+    permissions:
+      contents: read
+      packages: write
+    steps:
+      - uses: actions/checkout@v4
+{build}"#
+            )
+        }
+        _ => r#"stages:
+  - build
+
+build:
+  stage: build
+  image: docker:24
+  services:
+    - docker:24-dind
+  variables:
+    DOCKER_BUILDKIT: "1"
+  before_script:
+    - echo "$CI_REGISTRY_PASSWORD" | docker login -u "$CI_REGISTRY_USER" --password-stdin "$CI_REGISTRY"
+  script:
+    - docker build --target production --build-arg REVISION=$CI_COMMIT_SHA -t "$CI_REGISTRY_IMAGE:$CI_COMMIT_SHORT_SHA" .
+    - docker push "$CI_REGISTRY_IMAGE:$CI_COMMIT_SHORT_SHA"
+"#
+        .to_string(),
+    };
+    if let Some((id, source)) = secret {
+        let variable = id.to_uppercase();
+        contents = match (provider, bake) {
+            // Bake reads the secret from the source path declared in docker-bake.hcl.
+            ("github", true) => contents.replace(
+                "      - uses: docker/bake-action@v4\n",
+                &format!(
+                    r#"      - name: Write registry credentials
+        run: |
+          mkdir -p "$(dirname "{source}")"
+          printf '%s' "$REGISTRY_CREDENTIALS" > "{source}"
+        env:
+          REGISTRY_CREDENTIALS: ${{{{ secrets.{variable} }}}}
+      - uses: docker/bake-action@v4
+"#
+                ),
+            ),
+            ("github", false) => format!(
+                "{contents}          secrets: |\n            {id}=${{{{ secrets.{variable} }}}}\n"
+            ),
+            _ => contents
+                .replace(
+                    "  script:\n",
+                    &format!("  # {variable} is a File type CI/CD variable with the registry credentials.\n  script:\n"),
+                )
+                .replace(
+                    "docker build --target production",
+                    &format!("docker build --target production --secret id={id},src=${variable}"),
+                ),
+        };
+    }
+    if let Some(dir) = std::path::Path::new(file).parent() {
+        create_dir_all(dir).unwrap();
+    }
+    create_file(file, &contents);
+    Some(file)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    static CWD: Mutex<()> = Mutex::new(());
+
+    struct Restore(PathBuf);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let _ = std::env::set_current_dir(&self.0);
+        }
+    }
+
+    /// Runs `test` inside a fresh temporary directory. The working directory is process wide, so
+    /// tests using it are serialized.
+    pub fn in_temp_dir<T>(test: impl FnOnce() -> T) -> T {
+        let _lock = CWD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let dir = std::env::temp_dir().join(format!(
+            "dockerforge-test-{}-{:?}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let _restore = Restore(std::env::current_dir().unwrap());
+        std::env::set_current_dir(&dir).unwrap();
+        let result = test();
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+
+    #[test]
+    fn bake_mounts_the_registry_secret() {
+        let secret = crate::languages::registry_secret("Javascript");
+        let bake = bake("app", &["linux/amd64"], secret);
+        assert!(bake.starts_with("variable \"HOME\" {\n  default = null\n}"));
+        assert!(
+            bake.contains("target \"_common\" {\n  context = \".\"\n")
+                && bake.contains("  secret = [\"id=npmrc,src=${HOME}/.npmrc\"]\n}"),
+            "{}",
+            bake
+        );
+        assert!(!super::bake("app", &["linux/amd64"], None).contains("secret"));
+    }
+
+    #[test]
+    fn ci_reads_the_registry_secret() {
+        let secret = Some(crate::languages::golang::REGISTRY_SECRET);
+        in_temp_dir(|| {
+            for (provider, bake, expected) in [
+                (
+                    "github",
+                    true,
+                    "REGISTRY_CREDENTIALS: ${{ secrets.GIT_CREDENTIALS }}",
+                ),
+                (
+                    "github",
+                    false,
+                    "git_credentials=${{ secrets.GIT_CREDENTIALS }}",
+                ),
+                (
+                    "gitlab",
+                    false,
+                    "--secret id=git_credentials,src=$GIT_CREDENTIALS",
+                ),
+            ] {
+                let file = write_ci(provider, bake, secret).unwrap();
+                let contents = read_file(file).unwrap();
+                assert!(contents.contains(expected), "{}", contents);
+            }
+        });
     }
 }